@@ -1,39 +1,583 @@
 use std::vec::Vec;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::str::FromStr;
 
-use crate::utils::read_data_lines;
-
-fn read_day1_input() -> Vec<i32> {
-    let lines = read_data_lines("day1.txt").unwrap();
-    let mut calories: i32 = 0;
-    let mut fruits: i32 = 0;
-    let mut calories_array = Vec::new();
-    for line in lines {
-        let line_string = line;
-        if line_string.trim().is_empty() {
-            calories_array.push(calories);
-            calories = 0;
-            fruits = 0;
+use crate::input::{Input, Lines};
+use crate::utils::{env_parse, input_source, AocResult, AocError, RecordGroups};
+
+// One elf's inventory, numbered from 1 in the order the elves appear.
+#[derive(Debug, Clone)]
+struct Elf {
+    index: usize,
+    items: Vec<u64>
+}
+
+impl Elf {
+    fn count(&self) -> usize {
+        self.items.len()
+    }
+    // Summed in u128, which cannot overflow for any input that fits in memory.
+    fn total(&self) -> u128 {
+        self.items.iter().map(|x| *x as u128).sum()
+    }
+    fn largest_item(&self) -> Option<u64> {
+        self.items.iter().max().copied()
+    }
+}
+
+// Reads elves one at a time from a calorie inventory, so that only the elf
+// currently being read is held in memory. Runs of blank lines are treated as
+// a single separator.
+fn read_elves(lines: Lines<'_>) -> impl Iterator<Item = AocResult<Elf>> + '_ {
+    RecordGroups::<u64>::new(lines)
+        .enumerate()
+        .map(|(idx, items)| items.map(|items| Elf { index: idx + 1, items }))
+}
+
+// Keeps the k elves with the most calories seen so far in a bounded min-heap,
+// along with any elves that have been pushed out but tie with the lowest of
+// them. Equal totals are ranked by which elf came first.
+struct TopK {
+    k: usize,
+    heap: BinaryHeap<Reverse<(u128, Reverse<usize>)>>,
+    ties: Vec<(u128, Reverse<usize>)>
+}
+
+impl TopK {
+    fn new(k: usize) -> Self {
+        TopK { k, heap: BinaryHeap::with_capacity(k + 1), ties: Vec::new() }
+    }
+    fn push(&mut self, elf: &Elf) {
+        if self.k == 0 {
+            return;
+        }
+        let entry = (elf.total(), Reverse(elf.index));
+        if self.heap.len() < self.k {
+            self.heap.push(Reverse(entry));
+            return;
+        }
+        let Reverse(lowest) = *self.heap.peek().unwrap();
+        if entry > lowest {
+            self.heap.pop();
+            self.heap.push(Reverse(entry));
+            let Reverse(new_lowest) = *self.heap.peek().unwrap();
+            if new_lowest.0 == lowest.0 {
+                self.ties.push(lowest);
+            } else {
+                self.ties.clear();
+            }
+        } else if entry.0 == lowest.0 {
+            self.ties.push(entry);
+        }
+    }
+    // (index, calories) of the top elves, most calories first.
+    fn top(&self) -> Vec<(usize, u128)> {
+        let mut top = self.heap.iter().map(|Reverse(x)| *x).collect::<Vec<_>>();
+        top.sort_by(|a, b| b.cmp(a));
+        top.into_iter().map(|(calories, Reverse(index))| (index, calories)).collect()
+    }
+    // Elves left out of the top k only because of the order they came in.
+    fn ties(&self) -> Vec<usize> {
+        let mut ties = self.ties.iter().map(|x| x.1.0).collect::<Vec<_>>();
+        ties.sort();
+        ties
+    }
+}
+
+// Running statistics over a stream of elves. Percentiles need every total,
+// so one number per elf is kept, but never the items themselves.
+struct CalorieStats {
+    totals: Vec<u128>,
+    calories: u128,
+    items: usize,
+    min_items: usize,
+    max_items: usize,
+    // highest total and the number of elves sharing it
+    most: u128,
+    most_shared_by: usize
+}
+
+impl CalorieStats {
+    fn new() -> Self {
+        CalorieStats { totals: Vec::new(), calories: 0, items: 0, min_items: usize::MAX, max_items: 0, most: 0, most_shared_by: 0 }
+    }
+    fn push(&mut self, elf: &Elf) {
+        let total = elf.total();
+        if self.totals.is_empty() || total > self.most {
+            self.most = total;
+            self.most_shared_by = 1;
+        } else if total == self.most {
+            self.most_shared_by += 1;
+        }
+        self.totals.push(total);
+        self.calories += total;
+        self.items += elf.count();
+        self.min_items = self.min_items.min(elf.count());
+        self.max_items = self.max_items.max(elf.count());
+    }
+    fn elves(&self) -> usize {
+        self.totals.len()
+    }
+    fn mean(&self) -> f64 {
+        self.calories as f64 / self.elves() as f64
+    }
+    fn mean_items(&self) -> f64 {
+        self.items as f64 / self.elves() as f64
+    }
+    // Call once all elves have been pushed.
+    fn sort(&mut self) {
+        self.totals.sort();
+    }
+    fn median(&self) -> f64 {
+        let n = self.totals.len();
+        if n % 2 == 1 {
+            self.totals[n / 2] as f64
         } else {
-            calories += line_string.parse::<i32>().unwrap();
-            fruits += 1;
+            (self.totals[n / 2 - 1] as f64 + self.totals[n / 2] as f64) / 2.0
+        }
+    }
+    // Nearest rank percentile, p in 0..=100.
+    fn percentile(&self, p: usize) -> u128 {
+        let rank = (p * self.totals.len()).div_ceil(100).max(1);
+        self.totals[rank - 1]
+    }
+}
+
+// Which elves day1_query lists.
+enum ElfQuery {
+    MoreItemsThan(usize),
+    MoreCaloriesThan(u128),
+    LargestItem,
+    Elf(usize)
+}
+
+impl FromStr for ElfQuery {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || AocError::new(&format!("Bad query: {:?}", s));
+        if s == "largest-item" {
+            return Ok(ElfQuery::LargestItem);
+        }
+        let (name, n) = s.split_once(':').ok_or_else(bad)?;
+        match name {
+            "items-over" => Ok(ElfQuery::MoreItemsThan(n.parse().map_err(|_| bad())?)),
+            "calories-over" => Ok(ElfQuery::MoreCaloriesThan(n.parse().map_err(|_| bad())?)),
+            "elf" => Ok(ElfQuery::Elf(n.parse().map_err(|_| bad())?)),
+            _ => Err(bad()),
+        }
+    }
+}
+
+impl ElfQuery {
+    fn select<'a>(&self, elves: &'a [Elf]) -> Vec<&'a Elf> {
+        match self {
+            ElfQuery::MoreItemsThan(n) => elves.iter().filter(|x| x.count() > *n).collect(),
+            ElfQuery::MoreCaloriesThan(n) => elves.iter().filter(|x| x.total() > *n).collect(),
+            ElfQuery::LargestItem => {
+                let largest = elves.iter().filter_map(Elf::largest_item).max();
+                elves.iter().filter(|x| largest.is_some() && x.largest_item() == largest).collect()
+            },
+            ElfQuery::Elf(index) => elves.iter().filter(|x| x.index == *index).collect(),
+        }
+    }
+}
+
+// An item in the inventory: which elf carries it (as a position in the list
+// of elves), where it is in their list, and its calories.
+#[derive(Clone, Copy)]
+struct ItemRef {
+    elf: usize,
+    position: usize,
+    calories: u64
+}
+
+fn all_items(elves: &[Elf]) -> Vec<ItemRef> {
+    elves.iter().enumerate()
+        .flat_map(|(elf, x)| x.items.iter().enumerate()
+            .map(move |(position, calories)| ItemRef { elf, position, calories: *calories }))
+        .collect()
+}
+
+// A share of the items between some number of elves, as lists of indices
+// into the items.
+type Bins = Vec<Vec<usize>>;
+
+fn bin_load(bin: &[usize], items: &[ItemRef]) -> u128 {
+    bin.iter().map(|x| items[*x].calories as u128).sum()
+}
+
+fn heaviest_load(bins: &Bins, items: &[ItemRef]) -> u128 {
+    bins.iter().map(|x| bin_load(x, items)).max().unwrap_or(0)
+}
+
+// No plan can do better than an even split or than the largest item.
+fn load_lower_bound(items: &[ItemRef], bins: usize) -> u128 {
+    let total: u128 = items.iter().map(|x| x.calories as u128).sum();
+    let largest = items.iter().map(|x| x.calories as u128).max().unwrap_or(0);
+    total.div_ceil(bins as u128).max(largest)
+}
+
+fn by_calories_descending(items: &[ItemRef]) -> Vec<usize> {
+    let mut order = (0..items.len()).collect::<Vec<_>>();
+    order.sort_by_key(|x| Reverse(items[*x].calories));
+    order
+}
+
+// Longest processing time first: largest item to the lightest bin.
+fn plan_lpt(items: &[ItemRef], bins: usize) -> Bins {
+    let mut plan: Bins = vec![Vec::new(); bins];
+    let mut loads = (0..bins).map(|x| Reverse((0u128, x))).collect::<BinaryHeap<_>>();
+    for item in by_calories_descending(items) {
+        let Reverse((load, bin)) = loads.pop().unwrap();
+        plan[bin].push(item);
+        loads.push(Reverse((load + items[item].calories as u128, bin)));
+    }
+    plan
+}
+
+// Multi-way Karmarkar-Karp differencing. Each item starts as a partial plan
+// of its own; the two partial plans with the greatest spread between their
+// heaviest and lightest bins are repeatedly merged, heaviest bin with
+// lightest, until only one remains. Partial plans only hold their non-empty
+// bins, the rest being empty, so memory stays linear in the items however
+// many bins there are.
+fn plan_karmarkar_karp(items: &[ItemRef], bins: usize) -> Bins {
+    // each partial plan's bins are kept heaviest first
+    let mut partials: Vec<Vec<(u128, Vec<usize>)>> = Vec::new();
+    let mut spreads = BinaryHeap::new();
+    for (i, item) in items.iter().enumerate() {
+        spreads.push((item.calories as u128, partials.len()));
+        partials.push(vec![(item.calories as u128, vec![i])]);
+    }
+    while spreads.len() > 1 {
+        let (_, a) = spreads.pop().unwrap();
+        let (_, b) = spreads.pop().unwrap();
+        let b = std::mem::take(&mut partials[b]);
+        let a = std::mem::take(&mut partials[a]);
+        // b's bins, lightest first, line up with the last of the bins
+        let b_start = bins - b.len();
+        let mut b = b.into_iter().rev();
+        let mut merged = Vec::with_capacity(a.len() + b.len());
+        for (i, (load, mut contents)) in a.into_iter().enumerate() {
+            if i < b_start {
+                merged.push((load, contents));
+            } else {
+                let (load_b, contents_b) = b.next().unwrap();
+                contents.extend(contents_b);
+                merged.push((load + load_b, contents));
+            }
+        }
+        merged.extend(b);
+        merged.sort_by_key(|x| Reverse(x.0));
+        let lightest = if merged.len() == bins { merged[bins - 1].0 } else { 0 };
+        spreads.push((merged[0].0 - lightest, partials.len()));
+        partials.push(merged);
+    }
+    let mut plan = match spreads.pop() {
+        Some((_, last)) => std::mem::take(&mut partials[last]).into_iter().map(|x| x.1).collect(),
+        None => Vec::new(),
+    };
+    plan.resize(bins, Vec::new());
+    plan
+}
+
+// Depth first branch and bound, placing the largest items first. Bins with
+// the same load are interchangeable, so only the first of them is tried.
+// The search gives up once it has visited its budget of nodes.
+struct ExactSearch<'a> {
+    items: &'a [ItemRef],
+    order: Vec<usize>,
+    lower_bound: u128,
+    loads: Vec<u128>,
+    current: Bins,
+    best_load: u128,
+    best: Bins,
+    nodes_left: usize
+}
+
+impl ExactSearch<'_> {
+    fn search(&mut self, k: usize) {
+        if self.best_load == self.lower_bound || self.nodes_left == 0 {
+            return;
+        }
+        self.nodes_left -= 1;
+        if k == self.order.len() {
+            self.best_load = self.loads.iter().copied().max().unwrap_or(0);
+            self.best = self.current.clone();
+            return;
+        }
+        let item = self.order[k];
+        let calories = self.items[item].calories as u128;
+        for bin in 0..self.loads.len() {
+            let load = self.loads[bin];
+            if load + calories >= self.best_load || self.loads[..bin].contains(&load) {
+                continue;
+            }
+            self.loads[bin] += calories;
+            self.current[bin].push(item);
+            self.search(k + 1);
+            self.current[bin].pop();
+            self.loads[bin] -= calories;
+        }
+    }
+}
+
+// Largest number of items the exact solver is used for.
+const EXACT_PLAN_LIMIT: usize = 24;
+
+// Nodes the exact solver may visit before giving up. Hard inventories near
+// the item limit would otherwise take hours.
+const EXACT_SEARCH_NODES: usize = 500_000;
+
+// Karmarkar-Karp takes time proportional to items times bins, so past this
+// much work auto planning uses LPT alone.
+const KK_PLAN_LIMIT: usize = 50_000_000;
+
+// Starts from the LPT plan, so it always returns at least that good a plan.
+// The plan is optimal unless the search ran out of nodes, in which case the
+// better of the search's best plan and Karmarkar-Karp's is returned along
+// with false.
+fn plan_exact(items: &[ItemRef], bins: usize, nodes: usize) -> (Bins, bool) {
+    let start = plan_lpt(items, bins);
+    let mut search = ExactSearch {
+        items,
+        order: by_calories_descending(items),
+        lower_bound: load_lower_bound(items, bins),
+        loads: vec![0; bins],
+        current: vec![Vec::new(); bins],
+        best_load: heaviest_load(&start, items),
+        best: start,
+        nodes_left: nodes
+    };
+    search.search(0);
+    if search.nodes_left > 0 || search.best_load == search.lower_bound {
+        return (search.best, true);
+    }
+    let kk = plan_karmarkar_karp(items, bins);
+    if heaviest_load(&kk, items) < search.best_load {
+        (kk, false)
+    } else {
+        (search.best, false)
+    }
+}
+
+// Decides which elf ends up with each bin, so that as few items as possible
+// have to move. Bins are matched greedily to the elf already carrying most
+// of their items.
+fn assign_bins(bins: &Bins, items: &[ItemRef]) -> Vec<usize> {
+    let mut overlaps = Vec::new();
+    for (bin, contents) in bins.iter().enumerate() {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for item in contents {
+            *counts.entry(items[*item].elf).or_insert(0) += 1;
         }
+        overlaps.extend(counts.into_iter().map(|(elf, count)| (count, bin, elf)));
     }
-    if fruits > 0 {
-        calories_array.push(calories);
+    overlaps.sort_by_key(|(count, bin, elf)| (Reverse(*count), *bin, *elf));
+    let mut owner = vec![None; bins.len()];
+    let mut taken = vec![false; bins.len()];
+    for (_, bin, elf) in overlaps {
+        if owner[bin].is_none() && !taken[elf] {
+            owner[bin] = Some(elf);
+            taken[elf] = true;
+        }
     }
-    return calories_array;
+    let mut free = (0..bins.len()).filter(|x| !taken[*x]);
+    owner.into_iter().map(|x| x.or_else(|| free.next()).unwrap()).collect()
+}
+
+enum PlanMethod {
+    Auto,
+    Exact,
+    Lpt,
+    KarmarkarKarp
+}
+
+impl FromStr for PlanMethod {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(PlanMethod::Auto),
+            "exact" => Ok(PlanMethod::Exact),
+            "lpt" => Ok(PlanMethod::Lpt),
+            "kk" => Ok(PlanMethod::KarmarkarKarp),
+            _ => Err(AocError::new(&format!("Bad plan method: {:?}", s))),
+        }
+    }
+}
+
+fn read_day1_input() -> AocResult<Vec<Elf>> {
+    let input = Input::open(input_source("day1.txt"))?;
+    read_elves(input.lines()?).collect()
+}
+
+pub fn day1() {
+    day1_part1();
+    day1_part2();
 }
 
 pub fn day1_part1() {
-    let calories_array = read_day1_input();
-    let max = calories_array.iter().enumerate().max_by_key(|x| x.1).unwrap().0;
-    println!("Most calorific elf: {}", max);
-    println!("Most calories on an elf: {}", calories_array[max]);
+    let elves = read_day1_input().unwrap();
+    let Some(elf) = elves.iter().max_by_key(|x| (x.total(), Reverse(x.index))) else {
+        println!("No elves");
+        return;
+    };
+    println!("Most calorific elf: {}", elf.index);
+    println!("Most calories on an elf: {}", elf.total());
 }
 
 pub fn day1_part2() {
-    let mut calories_array = read_day1_input();
-    calories_array.sort();
-    calories_array.reverse();
-    println!("Calories of top 3 elves: {}", calories_array[0..3].iter().sum::<i32>())
-}
\ No newline at end of file
+    let input = Input::open(input_source("day1.txt")).unwrap();
+    let mut top = TopK::new(3);
+    for elf in read_elves(input.lines().unwrap()) {
+        top.push(&elf.unwrap());
+    }
+    let top = top.top();
+    println!("Calories of top {} elves: {}", top.len(), top.iter().map(|x| x.1).sum::<u128>())
+}
+
+// Streams the inventory once. DAY1_TOP sets how many of the top elves to
+// list (default 3).
+pub fn day1_stats() {
+    let k = env_parse::<usize>("DAY1_TOP", "3").unwrap();
+    let mut top = TopK::new(k);
+    let mut stats = CalorieStats::new();
+    let input = Input::open(input_source("day1.txt")).unwrap();
+    for elf in read_elves(input.lines().unwrap()) {
+        let elf = elf.unwrap();
+        top.push(&elf);
+        stats.push(&elf);
+    }
+    if stats.elves() == 0 {
+        println!("No elves");
+        return;
+    }
+    stats.sort();
+    println!("Elves: {}", stats.elves());
+    println!("Items: {} ({} to {} per elf, mean {:.2})", stats.items, stats.min_items, stats.max_items, stats.mean_items());
+    println!("Calories: {} (mean {:.2}, median {})", stats.calories, stats.mean(), stats.median());
+    let percentiles = [10, 25, 50, 75, 90, 99].iter()
+        .map(|p| format!("p{}={}", p, stats.percentile(*p)))
+        .collect::<Vec<_>>();
+    println!("Percentiles: {}", percentiles.join(" "));
+    println!("Most calories: {}, carried by {} elves", stats.most, stats.most_shared_by);
+    let top_elves = top.top();
+    println!("Top {} elves: {} calories", top_elves.len(), top_elves.iter().map(|x| x.1).sum::<u128>());
+    for (index, calories) in top_elves {
+        println!("  Elf {}: {}", index, calories);
+    }
+    let ties = top.ties();
+    if !ties.is_empty() {
+        let ties = ties.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        println!("Also tied with the last of the top elves: {}", ties.join(", "));
+    }
+}
+
+// Lists the elves matching DAY1_QUERY: "items-over:N", "calories-over:N",
+// "elf:N" or "largest-item" (the default).
+pub fn day1_query() {
+    let elves = read_day1_input().unwrap();
+    let query = env_parse::<ElfQuery>("DAY1_QUERY", "largest-item").unwrap();
+    let selected = query.select(&elves);
+    for elf in &selected {
+        println!("Elf {}: {} items, {} calories, largest item {}",
+            elf.index, elf.count(), elf.total(), elf.largest_item().unwrap_or(0));
+    }
+    println!("Matching elves: {}", selected.len());
+}
+
+// Shares the items out between the same elves so that the heaviest load is
+// as light as possible. DAY1_PLAN picks the method: "exact", "lpt", "kk"
+// (Karmarkar-Karp) or "auto", which is exact for small inventories, LPT for
+// large ones and otherwise the better of the two heuristics. The exact
+// search stops after EXACT_SEARCH_NODES nodes and settles for the best plan
+// it or Karmarkar-Karp found.
+pub fn day1_plan() {
+    let elves = read_day1_input().unwrap();
+    if elves.is_empty() {
+        println!("No elves");
+        return;
+    }
+    let method = env_parse::<PlanMethod>("DAY1_PLAN", "auto").unwrap();
+    let items = all_items(&elves);
+    let bins = match method {
+        PlanMethod::Exact | PlanMethod::Auto if items.len() <= EXACT_PLAN_LIMIT => {
+            let (bins, optimal) = plan_exact(&items, elves.len(), EXACT_SEARCH_NODES);
+            if !optimal {
+                println!("Exact search gave up after {} nodes, plan may not be optimal", EXACT_SEARCH_NODES);
+            }
+            bins
+        },
+        PlanMethod::Exact => {
+            println!("Too many items for the exact solver: {} (limit {})", items.len(), EXACT_PLAN_LIMIT);
+            return;
+        },
+        PlanMethod::Lpt => plan_lpt(&items, elves.len()),
+        PlanMethod::KarmarkarKarp => plan_karmarkar_karp(&items, elves.len()),
+        PlanMethod::Auto if items.len().saturating_mul(elves.len()) > KK_PLAN_LIMIT => plan_lpt(&items, elves.len()),
+        PlanMethod::Auto => [plan_lpt(&items, elves.len()), plan_karmarkar_karp(&items, elves.len())]
+            .into_iter()
+            .min_by_key(|x| heaviest_load(x, &items))
+            .unwrap(),
+    };
+    let owners = assign_bins(&bins, &items);
+    let mut moves = Vec::new();
+    for (bin, contents) in bins.iter().enumerate() {
+        for item in contents {
+            if items[*item].elf != owners[bin] {
+                moves.push((*item, owners[bin]));
+            }
+        }
+    }
+    moves.sort_by_key(|(item, to)| (items[*item].elf, items[*item].position, *to));
+    for (item, to) in &moves {
+        let item = &items[*item];
+        println!("Move item {} of elf {} ({} calories) to elf {}",
+            item.position + 1, elves[item.elf].index, item.calories, elves[*to].index);
+    }
+    let before = elves.iter().map(Elf::total).max().unwrap();
+    println!("Heaviest load before: {}", before);
+    println!("Heaviest load after: {}", heaviest_load(&bins, &items));
+    println!("Lower bound: {}", load_lower_bound(&items, elves.len()));
+    println!("Items moved: {} of {}", moves.len(), items.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Rng;
+    use std::time::{Duration, Instant};
+
+    fn items(calories: &[u64]) -> Vec<ItemRef> {
+        calories.iter().enumerate().map(|(i, x)| ItemRef { elf: i, position: 0, calories: *x }).collect()
+    }
+
+    fn placed(bins: &Bins, count: usize) -> bool {
+        let mut all = bins.iter().flatten().copied().collect::<Vec<_>>();
+        all.sort();
+        all == (0..count).collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn exact_plan_is_optimal() {
+        let items = items(&[3, 3, 2, 2, 2]);
+        let (bins, optimal) = plan_exact(&items, 2, EXACT_SEARCH_NODES);
+        assert!(optimal);
+        assert!(placed(&bins, items.len()));
+        assert_eq!(heaviest_load(&bins, &items), 6);
+    }
+
+    #[test]
+    fn exact_plan_at_the_limit_finishes() {
+        let mut rng = Rng::new(5);
+        let calories = (0..EXACT_PLAN_LIMIT).map(|_| rng.between(1000, 60000)).collect::<Vec<_>>();
+        let items = items(&calories);
+        let start = Instant::now();
+        let (bins, _) = plan_exact(&items, 8, EXACT_SEARCH_NODES);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(placed(&bins, items.len()));
+        assert!(heaviest_load(&bins, &items) >= load_lower_bound(&items, 8));
+        assert!(heaviest_load(&bins, &items) <= heaviest_load(&plan_lpt(&items, 8), &items));
+    }
+}
@@ -1,148 +1,728 @@
 use std::vec::Vec;
-use std::io;
+use std::cmp::{Ordering, Reverse};
+use std::io::{self, BufRead};
 use std::str::FromStr;
 
-use crate::utils::read_data_lines;
+use crate::input::Input;
+use crate::utils::{env_parse, input_source, parse_numbered, AocError, AocResult, Pattern, Rng, Source};
 
-#[derive(Clone, Copy)]
-enum RockPaperScissors {
-    ROCK,
-    PAPER,
-    SCISSORS,
-}
+// A weapon, as an index into its game's list of weapons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Weapon(usize);
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum RockPaperScissorsOutcome {
     WIN,
     LOSE,
     DRAW,
 }
 
+// A game defined by data: its weapons, which weapons beat which, and the score
+// for playing each weapon and for each outcome. In the strategy guide the
+// opponent's letter for weapon i is 'A' + i and the player's is 'X' + i,
+// wrapping round from Z to A, so classic guides still use A-C and X-Z.
+struct Game {
+    names: Vec<String>,
+    beats: Vec<Vec<bool>>,
+    weapon_scores: Vec<i32>,
+    // lose, draw, win
+    outcome_scores: [i32; 3],
+}
+
+// Each weapon needs a letter from A to Z, and a game needs something to
+// choose between.
+const MIN_WEAPONS: usize = 2;
+const MAX_WEAPONS: usize = 26;
+
+const RPSLS_GAME: &str = "\
+weapons Rock Paper Scissors Spock Lizard
+Rock beats Scissors Lizard
+Paper beats Rock Spock
+Scissors beats Paper Lizard
+Spock beats Scissors Rock
+Lizard beats Spock Paper
+";
+
+impl Game {
+    // Weapon i beats weapon j when (i - j) mod n is in 1..=(n - 1) / 2, so
+    // each weapon beats the (n - 1) / 2 before it and loses to the ones after
+    // it. With an even number of weapons, opposite weapons draw.
+    fn cyclic(names: &[&str]) -> Game {
+        let n = names.len();
+        Game {
+            names: names.iter().map(|x| x.to_string()).collect(),
+            beats: (0..n).map(|i| (0..n).map(|j| (1..=(n - 1) / 2).contains(&((i + n - j) % n))).collect()).collect(),
+            weapon_scores: (1..=n as i32).collect(),
+            outcome_scores: [0, 3, 6],
+        }
+    }
+    fn rock_paper_scissors() -> Game {
+        Game::cyclic(&["Rock", "Paper", "Scissors"])
+    }
+    fn rock_paper_scissors_lizard_spock() -> Game {
+        Game::read(Source::Text(RPSLS_GAME)).expect("Bad built-in game")
+    }
+    // A game file has a "weapons" line naming the weapons in letter order and
+    // optionally "scores" (one per weapon), "outcomes" (lose, draw and win)
+    // and "<weapon> beats <weapon>..." lines. Without any "beats" lines the
+    // weapons beat each other cyclically.
+    fn read(source: Source) -> AocResult<Game> {
+        let bad = |msg: &str, line: &str| AocError::new(&format!("{}: {:?}", msg, line));
+        let input = Input::open(source)?;
+        let mut game: Option<Game> = None;
+        let mut custom_beats = false;
+        parse_numbered(input.lines()?, |line| {
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            if tokens[0] == "weapons" {
+                let names = &tokens[1..];
+                if game.is_some() {
+                    return Err(bad("Weapons already given", line));
+                }
+                if !(MIN_WEAPONS..=MAX_WEAPONS).contains(&names.len()) {
+                    return Err(bad(&format!("Expected {} to {} weapons", MIN_WEAPONS, MAX_WEAPONS), line));
+                }
+                if names.iter().enumerate().any(|(i, x)| names[..i].contains(x)) {
+                    return Err(bad("Duplicate weapon", line));
+                }
+                game = Some(Game::cyclic(names));
+                return Ok(());
+            }
+            let game = game.as_mut().ok_or_else(|| bad("Expected weapons first", line))?;
+            let numbers = || tokens[1..].iter()
+                .map(|x| x.parse::<i32>().map_err(|_| bad("Bad score", line)))
+                .collect::<AocResult<Vec<i32>>>();
+            match tokens[0] {
+                "scores" => {
+                    game.weapon_scores = numbers()?;
+                    if game.weapon_scores.len() != game.names.len() {
+                        return Err(bad("Wrong number of scores", line));
+                    }
+                },
+                "outcomes" => {
+                    game.outcome_scores = numbers()?.try_into().map_err(|_| bad("Wrong number of scores", line))?;
+                },
+                _ if tokens.len() >= 2 && tokens[1] == "beats" => {
+                    if !custom_beats {
+                        game.beats = vec![vec![false; game.names.len()]; game.names.len()];
+                        custom_beats = true;
+                    }
+                    let me = game.weapon_named(tokens[0]).ok_or_else(|| bad("Unknown weapon", line))?;
+                    for name in &tokens[2..] {
+                        let thee = game.weapon_named(name).ok_or_else(|| bad("Unknown weapon", line))?;
+                        if game.beats[thee.0][me.0] || me == thee {
+                            return Err(bad("Contradictory rule", line));
+                        }
+                        game.beats[me.0][thee.0] = true;
+                    }
+                },
+                _ => return Err(bad("Bad game line", line)),
+            }
+            Ok(())
+        }).collect::<AocResult<Vec<()>>>()?;
+        game.ok_or(AocError::new("No weapons"))
+    }
+    fn weapons(&self) -> impl Iterator<Item = Weapon> {
+        (0..self.names.len()).map(Weapon)
+    }
+    fn weapon_named(&self, name: &str) -> Option<Weapon> {
+        self.names.iter().position(|x| x == name).map(Weapon)
+    }
+    fn name(&self, weapon: Weapon) -> &str {
+        &self.names[weapon.0]
+    }
+    fn weapon_score(&self, weapon: Weapon) -> i32 {
+        self.weapon_scores[weapon.0]
+    }
+    fn outcome_score(&self, x: RockPaperScissorsOutcome) -> i32 {
+        match x {
+            RockPaperScissorsOutcome::LOSE => self.outcome_scores[0],
+            RockPaperScissorsOutcome::DRAW => self.outcome_scores[1],
+            RockPaperScissorsOutcome::WIN => self.outcome_scores[2],
+        }
+    }
+    fn outcome(&self, me: Weapon, thee: Weapon) -> RockPaperScissorsOutcome {
+        if self.beats[me.0][thee.0] {
+            RockPaperScissorsOutcome::WIN
+        } else if self.beats[thee.0][me.0] {
+            RockPaperScissorsOutcome::LOSE
+        } else {
+            RockPaperScissorsOutcome::DRAW
+        }
+    }
+    // The best scoring weapon that gets the target outcome, if any does.
+    fn achieve(&self, thee: Weapon, target: RockPaperScissorsOutcome) -> Option<Weapon> {
+        self.weapons()
+            .filter(|me| self.outcome(*me, thee) == target)
+            .max_by_key(|me| self.weapon_score(*me))
+    }
+    fn opponent_letter(&self, letter: &str) -> AocResult<Weapon> {
+        match letter.as_bytes() {
+            [c] if c.is_ascii_uppercase() && ((c - b'A') as usize) < self.names.len() => Ok(Weapon((c - b'A') as usize)),
+            _ => Err(AocError::new(&format!("Bad opponent letter: {:?}", letter))),
+        }
+    }
+    // The player's column, 0 for X, 1 for Y and so on.
+    fn player_letter(&self, letter: &str) -> AocResult<usize> {
+        match letter.as_bytes() {
+            [c] if c.is_ascii_uppercase() && Game::column(*c) < self.names.len() => Ok(Game::column(*c)),
+            _ => Err(AocError::new(&format!("Bad player letter: {:?}", letter))),
+        }
+    }
+    fn column(letter: u8) -> usize {
+        (letter as usize + 26 - b'X' as usize) % 26
+    }
+    fn player_letter_name(column: usize) -> char {
+        (b'A' + ((b'X' - b'A') as usize + column) as u8 % 26) as char
+    }
+}
+
+// The strategy guide's second column is kept as a raw letter, X, Y, Z...
+// (0, 1, 2...); what it means depends on the interpretation used.
 struct RockPaperScissorsRound {
-    me: RockPaperScissors,
-    thee: RockPaperScissors,
-    target: RockPaperScissorsOutcome,
+    thee: Weapon,
+    column: usize,
 }
 
-impl FromStr for RockPaperScissors {
-    type Err = io::Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "A" => Ok(RockPaperScissors::ROCK),
-            "B" => Ok(RockPaperScissors::PAPER),
-            "C" => Ok(RockPaperScissors::SCISSORS),
-            "X" => Ok(RockPaperScissors::ROCK),
-            "Y" => Ok(RockPaperScissors::PAPER),
-            "Z" => Ok(RockPaperScissors::SCISSORS),
-            _ => Err(Self::Err::new(io::ErrorKind::InvalidData, "Bad character")),
+// A way of reading the second column of the strategy guide.
+trait StrategyInterpretation {
+    fn my_move(&self, game: &Game, thee: Weapon, column: usize) -> Option<Weapon>;
+    fn describe(&self, game: &Game) -> String;
+}
+
+// The letter says which weapon to play.
+struct MoveInterpretation(Vec<Weapon>);
+
+// The letter says how the round needs to end.
+struct OutcomeInterpretation(Vec<RockPaperScissorsOutcome>);
+
+impl StrategyInterpretation for MoveInterpretation {
+    fn my_move(&self, _game: &Game, _thee: Weapon, column: usize) -> Option<Weapon> {
+        self.0.get(column).copied()
+    }
+    fn describe(&self, game: &Game) -> String {
+        let parts = self.0.iter().enumerate()
+            .map(|(i, x)| format!("{}={}", Game::player_letter_name(i), game.name(*x)))
+            .collect::<Vec<_>>();
+        format!("moves {}", parts.join(" "))
+    }
+}
+
+impl StrategyInterpretation for OutcomeInterpretation {
+    fn my_move(&self, game: &Game, thee: Weapon, column: usize) -> Option<Weapon> {
+        game.achieve(thee, *self.0.get(column)?)
+    }
+    fn describe(&self, _game: &Game) -> String {
+        let parts = self.0.iter().enumerate()
+            .map(|(i, x)| format!("{}={:?}", Game::player_letter_name(i), x))
+            .collect::<Vec<_>>();
+        format!("outcomes {}", parts.join(" "))
+    }
+}
+
+const OUTCOMES: [RockPaperScissorsOutcome; 3] = [RockPaperScissorsOutcome::LOSE, RockPaperScissorsOutcome::DRAW, RockPaperScissorsOutcome::WIN];
+
+// Searching move permutations is factorial in the number of weapons.
+const MAX_SEARCHED_WEAPONS: usize = 8;
+
+// Every way of assigning the values to the letters, each used once.
+// Mappings that reuse a value are not considered: "always win" would
+// trivially score best.
+fn all_permutations<T: Copy>(values: &[T]) -> Vec<Vec<T>> {
+    if values.len() <= 1 {
+        return vec![values.to_vec()];
+    }
+    let mut ret = Vec::new();
+    for i in 0..values.len() {
+        let mut rest = values.to_vec();
+        let first = rest.remove(i);
+        for mut tail in all_permutations(&rest) {
+            tail.insert(0, first);
+            ret.push(tail);
         }
     }
+    ret
 }
 
-impl FromStr for RockPaperScissorsOutcome {
-    type Err = io::Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "X" => Ok(RockPaperScissorsOutcome::LOSE),
-            "Y" => Ok(RockPaperScissorsOutcome::DRAW),
-            "Z" => Ok(RockPaperScissorsOutcome::WIN),
-            _ => Err(Self::Err::new(io::ErrorKind::InvalidData, "Bad character")),
+fn all_interpretations(game: &Game) -> Vec<Box<dyn StrategyInterpretation>> {
+    let mut ret: Vec<Box<dyn StrategyInterpretation>> = Vec::new();
+    if game.names.len() <= MAX_SEARCHED_WEAPONS {
+        for mapping in all_permutations(&game.weapons().collect::<Vec<_>>()) {
+            ret.push(Box::new(MoveInterpretation(mapping)));
         }
     }
+    for mapping in all_permutations(&OUTCOMES) {
+        ret.push(Box::new(OutcomeInterpretation(mapping)));
+    }
+    ret
+}
+
+const ROUND: Pattern = Pattern("{opponent} {player}");
+
+impl RockPaperScissorsRound {
+    fn parse(s: &str, game: &Game) -> AocResult<Self> {
+        let fields = ROUND.captures(s)?;
+        let thee = fields.with(0, |x| game.opponent_letter(x))?;
+        let column = fields.with(1, |x| game.player_letter(x))?;
+        Ok(RockPaperScissorsRound { thee, column })
+    }
 }
 
 impl FromStr for RockPaperScissorsRound {
-    type Err = io::Error;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let tokens = s.split(' ').collect::<Vec<&str>>();
-        if tokens.len() != 2 {
-            return Err(Self::Err::new(io::ErrorKind::InvalidData, "Bad round"));
+        RockPaperScissorsRound::parse(s, &Game::rock_paper_scissors())
+    }
+}
+
+impl RockPaperScissorsRound {
+    fn play(&self, game: &Game, interpretation: &dyn StrategyInterpretation) -> Option<RoundResult> {
+        let me = interpretation.my_move(game, self.thee, self.column)?;
+        let outcome = game.outcome(me, self.thee);
+        Some(RoundResult {
+            thee: self.thee,
+            column: self.column,
+            me,
+            outcome,
+            shape_score: game.weapon_score(me),
+            outcome_score: game.outcome_score(outcome)
+        })
+    }
+    fn score_with(&self, game: &Game, interpretation: &dyn StrategyInterpretation) -> Option<i32> {
+        self.play(game, interpretation).map(|x| x.score())
+    }
+    fn score(&self, game: &Game) -> i32 {
+        self.score_with(game, &MoveInterpretation(game.weapons().collect())).unwrap()
+    }
+    fn score2(&self, game: &Game) -> Option<i32> {
+        self.score_with(game, &OutcomeInterpretation(OUTCOMES.to_vec()))
+    }
+}
+
+// How one round of the guide went under some interpretation.
+struct RoundResult {
+    thee: Weapon,
+    column: usize,
+    me: Weapon,
+    outcome: RockPaperScissorsOutcome,
+    shape_score: i32,
+    outcome_score: i32
+}
+
+impl RoundResult {
+    fn score(&self) -> i32 {
+        self.shape_score + self.outcome_score
+    }
+}
+
+// Summary of a whole guide played under one interpretation.
+struct GuideStats {
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    shape_score: i32,
+    outcome_score: i32,
+    // most frequent opponent weapon and how often it was played
+    favourite: Option<(Weapon, usize)>,
+    longest_streak: usize
+}
+
+impl GuideStats {
+    fn new(game: &Game, results: &[RoundResult]) -> Self {
+        let mut stats = GuideStats {
+            wins: 0, draws: 0, losses: 0, shape_score: 0, outcome_score: 0, favourite: None, longest_streak: 0
+        };
+        let mut counts = vec![0; game.names.len()];
+        let mut streak = 0;
+        for result in results {
+            match result.outcome {
+                RockPaperScissorsOutcome::WIN => stats.wins += 1,
+                RockPaperScissorsOutcome::DRAW => stats.draws += 1,
+                RockPaperScissorsOutcome::LOSE => stats.losses += 1,
+            }
+            if result.outcome == RockPaperScissorsOutcome::WIN {
+                streak += 1;
+                stats.longest_streak = stats.longest_streak.max(streak);
+            } else {
+                streak = 0;
+            }
+            stats.shape_score += result.shape_score;
+            stats.outcome_score += result.outcome_score;
+            counts[result.thee.0] += 1;
         }
-        let me = tokens[1].parse::<RockPaperScissors>()?;
-        let thee = tokens[0].parse::<RockPaperScissors>()?;
-        let target = tokens[1].parse::<RockPaperScissorsOutcome>()?;
-        Ok(RockPaperScissorsRound { me: me, thee: thee, target: target})
+        stats.favourite = (0..counts.len())
+            .max_by_key(|i| (counts[*i], Reverse(*i)))
+            .filter(|i| counts[*i] > 0)
+            .map(|i| (Weapon(i), counts[i]));
+        stats
     }
+    fn score(&self) -> i32 {
+        self.shape_score + self.outcome_score
+    }
+}
+
+// A player in a tournament. History is (my weapon, their weapon) for each
+// round played so far.
+trait Bot {
+    fn name(&self) -> String;
+    fn choose(&mut self, game: &Game, history: &[(Weapon, Weapon)]) -> Weapon;
 }
 
-fn action_score(x: RockPaperScissors) -> i32 {
-    match x {
-        RockPaperScissors::ROCK => 1,
-        RockPaperScissors::PAPER => 2,
-        RockPaperScissors::SCISSORS => 3,
+// The best scoring weapon that beats the predicted one, or failing that
+// draws with it.
+fn counter(game: &Game, predicted: Weapon) -> Weapon {
+    game.achieve(predicted, RockPaperScissorsOutcome::WIN)
+        .or(game.achieve(predicted, RockPaperScissorsOutcome::DRAW))
+        .unwrap_or(predicted)
+}
+
+struct RandomBot {
+    seed: u64,
+    rng: Rng
+}
+
+impl RandomBot {
+    fn new(seed: u64) -> Self {
+        RandomBot { seed, rng: Rng::new(seed) }
     }
 }
 
-fn outcome_score(x: RockPaperScissorsOutcome) -> i32 {
-    match x {
-        RockPaperScissorsOutcome::LOSE => 0,
-        RockPaperScissorsOutcome::DRAW => 3,
-        RockPaperScissorsOutcome::WIN => 6
+impl Bot for RandomBot {
+    fn name(&self) -> String {
+        format!("random({})", self.seed)
+    }
+    fn choose(&mut self, game: &Game, _history: &[(Weapon, Weapon)]) -> Weapon {
+        Weapon(self.rng.below(game.names.len() as u64) as usize)
     }
 }
 
-fn outcome(me: RockPaperScissors, thee: RockPaperScissors) -> RockPaperScissorsOutcome {
-    match me {
-        RockPaperScissors::ROCK => match thee {
-            RockPaperScissors::ROCK => RockPaperScissorsOutcome::DRAW,
-            RockPaperScissors::PAPER => RockPaperScissorsOutcome::LOSE,
-            RockPaperScissors::SCISSORS => RockPaperScissorsOutcome::WIN,
-        },
-        RockPaperScissors::PAPER => match thee {
-            RockPaperScissors::ROCK => RockPaperScissorsOutcome::WIN,
-            RockPaperScissors::PAPER => RockPaperScissorsOutcome::DRAW,
-            RockPaperScissors::SCISSORS => RockPaperScissorsOutcome::LOSE,
-        },
-        RockPaperScissors::SCISSORS => match thee {
-            RockPaperScissors::ROCK => RockPaperScissorsOutcome::LOSE,
-            RockPaperScissors::PAPER => RockPaperScissorsOutcome::WIN,
-            RockPaperScissors::SCISSORS => RockPaperScissorsOutcome::DRAW,
-        },
+// Counters the opponent's most frequent weapon so far.
+struct FrequencyBot;
+
+impl Bot for FrequencyBot {
+    fn name(&self) -> String {
+        "frequency".to_owned()
+    }
+    fn choose(&mut self, game: &Game, history: &[(Weapon, Weapon)]) -> Weapon {
+        let mut counts = vec![0; game.names.len()];
+        for (_, theirs) in history {
+            counts[theirs.0] += 1;
+        }
+        let likely = (0..counts.len()).max_by_key(|i| (counts[*i], Reverse(*i))).unwrap_or(0);
+        counter(game, Weapon(likely))
     }
 }
 
-fn achieve(thee: RockPaperScissors, target: RockPaperScissorsOutcome) -> RockPaperScissors {
-    match thee {
-        RockPaperScissors::ROCK => match target {
-            RockPaperScissorsOutcome::LOSE => RockPaperScissors::SCISSORS,
-            RockPaperScissorsOutcome::DRAW => RockPaperScissors::ROCK,
-            RockPaperScissorsOutcome::WIN => RockPaperScissors::PAPER,
-        },
-        RockPaperScissors::PAPER => match target {
-            RockPaperScissorsOutcome::LOSE => RockPaperScissors::ROCK,
-            RockPaperScissorsOutcome::DRAW => RockPaperScissors::PAPER,
-            RockPaperScissorsOutcome::WIN => RockPaperScissors::SCISSORS,
-        },
-        RockPaperScissors::SCISSORS => match target {
-            RockPaperScissorsOutcome::LOSE => RockPaperScissors::PAPER,
-            RockPaperScissorsOutcome::DRAW => RockPaperScissors::SCISSORS,
-            RockPaperScissorsOutcome::WIN => RockPaperScissors::ROCK,
-        },
+// Looks for the last time the opponent played their most recent sequence of
+// weapons and counters whatever they played next.
+struct PatternBot {
+    length: usize
+}
+
+impl Bot for PatternBot {
+    fn name(&self) -> String {
+        format!("pattern({})", self.length)
+    }
+    fn choose(&mut self, game: &Game, history: &[(Weapon, Weapon)]) -> Weapon {
+        let theirs = history.iter().map(|x| x.1).collect::<Vec<_>>();
+        for length in (1..=self.length.min(theirs.len())).rev() {
+            let recent = &theirs[theirs.len() - length..];
+            let found = (0..theirs.len() - length).rev()
+                .find(|start| &theirs[*start..*start + length] == recent);
+            if let Some(start) = found {
+                return counter(game, theirs[start + length]);
+            }
+        }
+        FrequencyBot.choose(game, history)
     }
 }
 
-impl RockPaperScissorsRound {
-    fn score(&self) -> i32 {
-        action_score(self.me) + outcome_score(outcome(self.me, self.thee))
+// Plays the strategy guide's moves in order, reading X, Y, Z... as weapons,
+// and starts again when they run out.
+struct GuideBot {
+    moves: Vec<Weapon>
+}
+
+impl Bot for GuideBot {
+    fn name(&self) -> String {
+        "guide".to_owned()
+    }
+    fn choose(&mut self, _game: &Game, history: &[(Weapon, Weapon)]) -> Weapon {
+        self.moves[history.len() % self.moves.len()]
+    }
+}
+
+struct MatchResult {
+    wins: [usize; 2],
+    draws: usize,
+    scores: [i32; 2]
+}
+
+fn play_match(game: &Game, bots: [&mut dyn Bot; 2], rounds: usize) -> MatchResult {
+    let [first, second] = bots;
+    let mut histories: [Vec<(Weapon, Weapon)>; 2] = [Vec::new(), Vec::new()];
+    let mut result = MatchResult { wins: [0, 0], draws: 0, scores: [0, 0] };
+    for _ in 0..rounds {
+        let a = first.choose(game, &histories[0]);
+        let b = second.choose(game, &histories[1]);
+        histories[0].push((a, b));
+        histories[1].push((b, a));
+        let outcome = game.outcome(a, b);
+        match outcome {
+            RockPaperScissorsOutcome::WIN => result.wins[0] += 1,
+            RockPaperScissorsOutcome::LOSE => result.wins[1] += 1,
+            RockPaperScissorsOutcome::DRAW => result.draws += 1,
+        }
+        result.scores[0] += game.weapon_score(a) + game.outcome_score(outcome);
+        result.scores[1] += game.weapon_score(b) + game.outcome_score(game.outcome(b, a));
     }
-    fn score2(&self) -> i32 {
-        outcome_score(self.target) + action_score(achieve(self.thee, self.target))
+    result
+}
+
+// The kinds of bot that can be played against: "random", "frequency",
+// "pattern" or "guide".
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BotKind {
+    Random,
+    Frequency,
+    Pattern,
+    Guide
+}
+
+const BOT_KINDS: [BotKind; 4] = [BotKind::Random, BotKind::Frequency, BotKind::Pattern, BotKind::Guide];
+
+impl FromStr for BotKind {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(BotKind::Random),
+            "frequency" => Ok(BotKind::Frequency),
+            "pattern" => Ok(BotKind::Pattern),
+            "guide" => Ok(BotKind::Guide),
+            _ => Err(AocError::new(&format!("Unknown bot: {:?}", s))),
+        }
     }
 }
 
-fn read_day2_input() -> io::Result<Vec<RockPaperScissorsRound>> {
-    let lines = read_data_lines("day2.txt")?;
-    lines.iter()
-        .map(|x| x.trim().to_owned())
-        .filter(|x| !x.is_empty())
-        .map(|x| x.parse::<RockPaperScissorsRound>() )
+// The guide bot needs a guide with at least one move in it.
+fn make_bot(kind: BotKind, game: &Game, rounds: &[RockPaperScissorsRound], seed: u64) -> AocResult<Box<dyn Bot>> {
+    Ok(match kind {
+        BotKind::Random => Box::new(RandomBot::new(seed)),
+        BotKind::Frequency => Box::new(FrequencyBot),
+        BotKind::Pattern => Box::new(PatternBot { length: 3 }),
+        BotKind::Guide => {
+            let guide = MoveInterpretation(game.weapons().collect());
+            let moves = rounds.iter()
+                .filter_map(|x| guide.my_move(game, x.thee, x.column))
+                .collect::<Vec<_>>();
+            if moves.is_empty() {
+                return Err(AocError::new("No guide moves for the guide bot"));
+            }
+            Box::new(GuideBot { moves })
+        },
+    })
+}
+
+// Every kind of bot that can be made from the guide.
+fn make_bots(game: &Game, rounds: &[RockPaperScissorsRound], seed: u64) -> Vec<Box<dyn Bot>> {
+    BOT_KINDS.iter()
+        .filter_map(|kind| make_bot(*kind, game, rounds, seed).ok())
         .collect()
 }
 
+// DAY2_GAME picks the rules: "rps" (the default), "rpsls", or "file:<name>"
+// for a game file.
+fn read_game() -> AocResult<Game> {
+    match std::env::var("DAY2_GAME").as_deref() {
+        Err(_) | Ok("rps") => Ok(Game::rock_paper_scissors()),
+        Ok("rpsls") => Ok(Game::rock_paper_scissors_lizard_spock()),
+        Ok(x) => match x.strip_prefix("file:") {
+            Some(filename) => Game::read(Source::File(filename)).map_err(|e| e.in_file(filename)),
+            None => Err(AocError::new(&format!("Unknown game: {}", x))),
+        },
+    }
+}
+
+fn read_day2_input(game: &Game) -> AocResult<Vec<RockPaperScissorsRound>> {
+    let input = Input::open(input_source("day2.txt"))?;
+    parse_numbered(input.lines()?, |x| RockPaperScissorsRound::parse(x, game)).collect()
+}
+
 pub fn day2() {
-    let rounds = read_day2_input().unwrap();
-    let score: i32 = rounds.iter().map(RockPaperScissorsRound::score).sum();
+    let game = read_game().unwrap();
+    let rounds = read_day2_input(&game).unwrap();
+    let score: i32 = rounds.iter().map(|x| x.score(&game)).sum();
     println!("Rock paper scissors score: {}", score);
-    let score2: i32 = rounds.iter().map(RockPaperScissorsRound::score2).sum();
-    println!("Rock paper scissors score2: {}", score2);
-}
\ No newline at end of file
+    match rounds.iter().map(|x| x.score2(&game)).sum::<Option<i32>>() {
+        Some(score2) => println!("Rock paper scissors score2: {}", score2),
+        None if rounds.iter().any(|x| x.column >= OUTCOMES.len()) => println!("Rock paper scissors score2: guide uses more than three outcome letters"),
+        None => println!("Rock paper scissors score2: no weapon achieves that outcome"),
+    }
+}
+
+pub fn day2_solve() {
+    let game = read_game().unwrap();
+    let rounds = read_day2_input(&game).unwrap();
+    if game.names.len() > MAX_SEARCHED_WEAPONS {
+        println!("Too many weapons to search move mappings, only trying outcomes");
+    }
+    let scored = all_interpretations(&game).into_iter()
+        .filter_map(|x| rounds.iter()
+            .map(|r| r.score_with(&game, x.as_ref()))
+            .sum::<Option<i32>>()
+            .map(|score| (score, x)))
+        .collect::<Vec<_>>();
+    for (score, interpretation) in &scored {
+        println!("{}: {}", interpretation.describe(&game), score);
+    }
+    match scored.iter().max_by_key(|x| x.0) {
+        Some((best, interpretation)) => println!("Best interpretation: {} scoring {}", interpretation.describe(&game), best),
+        None => println!("Best interpretation: no interpretation covers this guide"),
+    }
+}
+
+// Round robin between the bots. DAY2_ROUNDS sets the rounds per match and
+// DAY2_SEED seeds the random bot.
+pub fn day2_tournament() {
+    let game = read_game().unwrap();
+    let rounds = read_day2_input(&game).unwrap();
+    let mut bots = make_bots(&game, &rounds, env_parse("DAY2_SEED", "1").unwrap());
+    let num_rounds = env_parse::<usize>("DAY2_ROUNDS", "1000").unwrap();
+    let mut points = vec![0; bots.len()];
+    let mut totals = vec![0; bots.len()];
+    for i in 0..bots.len() {
+        for j in (i + 1)..bots.len() {
+            let (left, right) = bots.split_at_mut(j);
+            let result = play_match(&game, [left[i].as_mut(), right[0].as_mut()], num_rounds);
+            println!("{} vs {}: {}-{} with {} draws, scores {}-{}",
+                left[i].name(), right[0].name(), result.wins[0], result.wins[1], result.draws,
+                result.scores[0], result.scores[1]);
+            totals[i] += result.scores[0];
+            totals[j] += result.scores[1];
+            match result.wins[0].cmp(&result.wins[1]) {
+                Ordering::Greater => points[i] += 2,
+                Ordering::Less => points[j] += 2,
+                Ordering::Equal => {
+                    points[i] += 1;
+                    points[j] += 1;
+                },
+            }
+        }
+    }
+    let mut order = (0..bots.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| (Reverse(points[*i]), Reverse(totals[*i])));
+    println!("Standings:");
+    for i in order {
+        println!("  {}: {} points, total score {}", bots[i].name(), points[i], totals[i]);
+    }
+}
+
+// Play against a bot on the terminal, typing a player letter (X, Y, Z...) or
+// a weapon name each round. DAY2_BOT chooses the opponent: "random",
+// "frequency" (the default), "pattern" or "guide".
+pub fn day2_play() {
+    let game = read_game().unwrap();
+    let rounds = read_day2_input(&game).unwrap_or_default();
+    let kind = env_parse::<BotKind>("DAY2_BOT", "frequency").unwrap();
+    let mut bot = make_bot(kind, &game, &rounds, env_parse("DAY2_SEED", "1").unwrap()).unwrap();
+    let choices = game.weapons()
+        .map(|w| format!("{}={}", Game::player_letter_name(w.0), game.name(w)))
+        .collect::<Vec<_>>();
+    println!("Playing {} against {}. Enter {} or q to quit.", game.names.join("/"), bot.name(), choices.join(" "));
+    let mut history: Vec<(Weapon, Weapon)> = Vec::new();
+    let mut scores = [0, 0];
+    for line in io::stdin().lock().lines() {
+        let line = line.unwrap();
+        let line = line.trim();
+        if line == "q" {
+            break;
+        }
+        let mine = match game.player_letter(&line.to_uppercase()) {
+            Ok(column) => Weapon(column),
+            Err(_) => match game.weapons().find(|w| game.name(*w).eq_ignore_ascii_case(line)) {
+                Some(w) => w,
+                None => {
+                    println!("Unknown weapon: {:?}", line);
+                    continue;
+                },
+            },
+        };
+        let theirs = bot.choose(&game, &history.iter().map(|(a, b)| (*b, *a)).collect::<Vec<_>>());
+        history.push((mine, theirs));
+        let outcome = game.outcome(mine, theirs);
+        scores[0] += game.weapon_score(mine) + game.outcome_score(outcome);
+        scores[1] += game.weapon_score(theirs) + game.outcome_score(game.outcome(theirs, mine));
+        println!("{} vs {}: {:?}. Score {}-{}", game.name(mine), game.name(theirs), outcome, scores[0], scores[1]);
+    }
+}
+
+// How day2_report prints: text, one csv row per interpretation, or one csv
+// row per round of the guide.
+#[derive(PartialEq, Eq)]
+enum ReportFormat {
+    Text,
+    Csv,
+    CsvRounds
+}
+
+impl FromStr for ReportFormat {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "csv" => Ok(ReportFormat::Csv),
+            "csv-rounds" => Ok(ReportFormat::CsvRounds),
+            _ => Err(AocError::new(&format!("Bad report format: {:?}", s))),
+        }
+    }
+}
+
+// Statistics for the guide under both of the puzzle's interpretations.
+// DAY2_FORMAT is "text" (the default), "csv" or "csv-rounds"; see
+// ReportFormat.
+pub fn day2_report() {
+    let game = read_game().unwrap();
+    let rounds = read_day2_input(&game).unwrap();
+    let format = env_parse::<ReportFormat>("DAY2_FORMAT", "text").unwrap();
+    let interpretations: [(&str, Box<dyn StrategyInterpretation>); 2] = [
+        ("moves", Box::new(MoveInterpretation(game.weapons().collect()))),
+        ("outcomes", Box::new(OutcomeInterpretation(OUTCOMES.to_vec()))),
+    ];
+    match format {
+        ReportFormat::Csv => println!("interpretation,wins,draws,losses,shape_score,outcome_score,score,favourite_opponent_move,favourite_count,longest_winning_streak"),
+        ReportFormat::CsvRounds => println!("interpretation,round,opponent,column,me,outcome,shape_score,outcome_score,score"),
+        ReportFormat::Text => {},
+    }
+    for (label, interpretation) in &interpretations {
+        let results = rounds.iter()
+            .map(|x| x.play(&game, interpretation.as_ref()))
+            .collect::<Option<Vec<_>>>();
+        let Some(results) = results else {
+            if format == ReportFormat::Text {
+                println!("{}: guide uses letters this interpretation does not cover", interpretation.describe(&game));
+            }
+            continue;
+        };
+        let stats = GuideStats::new(&game, &results);
+        let (favourite, favourite_count) = match stats.favourite {
+            Some((weapon, count)) => (game.name(weapon), count),
+            None => ("", 0),
+        };
+        match format {
+            ReportFormat::Csv => println!("{},{},{},{},{},{},{},{},{},{}",
+                label, stats.wins, stats.draws, stats.losses, stats.shape_score, stats.outcome_score,
+                stats.score(), favourite, favourite_count, stats.longest_streak),
+            ReportFormat::CsvRounds => for (i, result) in results.iter().enumerate() {
+                println!("{},{},{},{},{},{:?},{},{},{}",
+                    label, i + 1, game.name(result.thee), Game::player_letter_name(result.column),
+                    game.name(result.me), result.outcome, result.shape_score, result.outcome_score, result.score());
+            },
+            ReportFormat::Text => {
+                println!("{}:", interpretation.describe(&game));
+                println!("  Wins: {}, draws: {}, losses: {}", stats.wins, stats.draws, stats.losses);
+                println!("  Score: {} ({} from shapes, {} from outcomes)", stats.score(), stats.shape_score, stats.outcome_score);
+                println!("  Most frequent opponent move: {} ({} times)", favourite, favourite_count);
+                println!("  Longest winning streak: {}", stats.longest_streak);
+            },
+        }
+    }
+}
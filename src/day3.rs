@@ -1,119 +1,523 @@
 use std::str::FromStr;
-use std::io;
-use std::vec::Vec;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::ops::{BitAnd, BitOr, Sub};
 
-use crate::utils::read_data_lines;
+use crate::input::Input;
+use crate::utils::{env_parse, input_source, parse_chars, parse_numbered, AocError, AocResult, Pattern, Source};
 
+// A set of items, one bit per item's index in the priority table. Tables of
+// up to 64 items, such as the puzzle's, fit in a single word.
+#[derive(Clone)]
+enum ItemSet {
+    Small(u64),
+    Large(Vec<u64>)
+}
+
+impl Debug for ItemSet {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_set().entries(self.indices()).finish()
+    }
+}
+
+impl Default for ItemSet {
+    fn default() -> Self {
+        ItemSet::Small(0)
+    }
+}
+
+impl ItemSet {
+    // An empty set with room for every item in a table of the given size.
+    fn with_capacity(num_items: usize) -> Self {
+        if num_items <= 64 {
+            ItemSet::Small(0)
+        } else {
+            ItemSet::Large(vec![0; num_items.div_ceil(64)])
+        }
+    }
+    fn words(&self) -> &[u64] {
+        match self {
+            ItemSet::Small(bits) => std::slice::from_ref(bits),
+            ItemSet::Large(words) => words,
+        }
+    }
+    fn insert(&mut self, index: usize) {
+        match self {
+            ItemSet::Small(bits) => *bits |= 1 << index,
+            ItemSet::Large(words) => words[index / 64] |= 1 << (index % 64),
+        }
+    }
+    fn contains(&self, index: usize) -> bool {
+        self.words().get(index / 64).is_some_and(|x| x & (1 << (index % 64)) != 0)
+    }
+    fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words().iter().enumerate().flat_map(|(word, bits)| (0..64)
+            .filter(move |bit| bits & (1 << bit) != 0)
+            .map(move |bit| word * 64 + bit))
+    }
+    fn priority_sum(&self, table: &PriorityTable) -> i32 {
+        self.indices().map(|x| table.priority(x)).sum()
+    }
+    fn len(&self) -> u32 {
+        self.words().iter().map(|x| x.count_ones()).sum()
+    }
+    fn combine(&self, that: &ItemSet, op: fn(u64, u64) -> u64) -> ItemSet {
+        if let (ItemSet::Small(a), ItemSet::Small(b)) = (self, that) {
+            return ItemSet::Small(op(*a, *b));
+        }
+        let len = self.words().len().max(that.words().len());
+        let word = |set: &ItemSet, i: usize| set.words().get(i).copied().unwrap_or(0);
+        ItemSet::Large((0..len).map(|i| op(word(self, i), word(that, i))).collect())
+    }
+}
+
+impl BitAnd for &ItemSet {
+    type Output = ItemSet;
+    fn bitand(self, that: &ItemSet) -> ItemSet {
+        self.combine(that, |a, b| a & b)
+    }
+}
+
+impl BitOr for &ItemSet {
+    type Output = ItemSet;
+    fn bitor(self, that: &ItemSet) -> ItemSet {
+        self.combine(that, |a, b| a | b)
+    }
+}
+
+impl Sub for &ItemSet {
+    type Output = ItemSet;
+    fn sub(self, that: &ItemSet) -> ItemSet {
+        self.combine(that, |a, b| a & !b)
+    }
+}
+
+const DEFAULT_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+// Maps items to priorities and back. By default a-z are 1-26 and A-Z are
+// 27-52, but any set of chars can be given priorities. Each item also gets a
+// dense index, in the order it was added, which is its bit in an ItemSet.
+struct PriorityTable {
+    indices: HashMap<char, usize>,
+    owners: HashMap<i32, char>,
+    items: Vec<(char, i32)>
+}
+
+const PRIORITY_ENTRY: Pattern = Pattern("{item} {priority}");
+
+impl PriorityTable {
+    fn new() -> Self {
+        PriorityTable { indices: HashMap::new(), owners: HashMap::new(), items: Vec::new() }
+    }
+    // Items get priorities 1, 2, 3... in the order they appear.
+    fn from_alphabet(alphabet: &str) -> AocResult<Self> {
+        let mut ret = PriorityTable::new();
+        for (idx, c) in alphabet.chars().enumerate() {
+            ret.insert(c, idx as i32 + 1)?;
+        }
+        Ok(ret)
+    }
+    // Reads lines of the form "<item> <priority>".
+    fn read(filename: &str) -> AocResult<Self> {
+        let mut ret = PriorityTable::new();
+        let input = Input::open(Source::File(filename))?;
+        parse_numbered(input.lines()?, |line| {
+            let fields = PRIORITY_ENTRY.captures(line)?;
+            let item = fields.with(0, |x| {
+                let mut chars = x.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(c),
+                    _ => Err(AocError::new(&format!("Bad item: {:?}", x))),
+                }
+            })?;
+            ret.insert(item, fields.parse::<i32>(1)?)
+        }).collect::<AocResult<Vec<()>>>()?;
+        Ok(ret)
+    }
+    fn insert(&mut self, item: char, priority: i32) -> AocResult<()> {
+        if priority <= 0 {
+            return Err(AocError::new(&format!("Bad priority for {:?}: {}", item, priority)));
+        }
+        if self.indices.contains_key(&item) {
+            return Err(AocError::new(&format!("Duplicate item: {:?}", item)));
+        }
+        if let Some(owner) = self.owners.get(&priority) {
+            return Err(AocError::new(&format!("Duplicate priority {}: {:?} and {:?}", priority, owner, item)));
+        }
+        self.indices.insert(item, self.items.len());
+        self.owners.insert(priority, item);
+        self.items.push((item, priority));
+        Ok(())
+    }
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+    fn index(&self, item: char) -> Option<usize> {
+        self.indices.get(&item).copied()
+    }
+    fn item(&self, index: usize) -> char {
+        self.items[index].0
+    }
+    fn priority(&self, index: usize) -> i32 {
+        self.items[index].1
+    }
+}
+
+impl Default for PriorityTable {
+    fn default() -> Self {
+        PriorityTable::from_alphabet(DEFAULT_ALPHABET).unwrap()
+    }
+}
+
+#[derive(Debug)]
 struct Compartment {
     contents_str: String,
-    contents: Vec<i32>
+    contents: ItemSet
 }
 
+#[derive(Debug)]
 struct Backpack {
     original: String,
-    contents: Vec<i32>,
+    contents: ItemSet,
     left: Compartment,
     right: Compartment
 }
 
-fn intersection(a: Vec<i32>, b: Vec<i32>) -> Vec<i32> {
-    // note: SLOW
-    let mut ret: Vec<i32> = Vec::new();
-    for l in &a {
-        for r in &b {
-            if *l == *r {
-                ret.push(*l);
-            }
+impl Backpack {
+    fn common(&self) -> ItemSet {
+        &self.left.contents & &self.right.contents
+    }
+}
+
+// Text or json description of one backpack, with its items' priorities
+// looked up in the table it was parsed with.
+struct BackpackReport<'a> {
+    index: usize,
+    pack: &'a Backpack,
+    table: &'a PriorityTable
+}
+
+impl BackpackReport<'_> {
+    fn ordinals(&self, s: &str) -> Vec<i32> {
+        s.chars().filter_map(|c| self.table.index(c)).map(|x| self.table.priority(x)).collect()
+    }
+    fn shared(&self) -> Vec<(char, i32)> {
+        self.pack.common().indices()
+            .map(|x| (self.table.item(x), self.table.priority(x)))
+            .collect()
+    }
+    fn compartment_json(&self, compartment: &Compartment) -> String {
+        let ordinals = self.ordinals(&compartment.contents_str).iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        format!("{{\"contents\":{},\"ordinals\":[{}]}}", json_string(&compartment.contents_str), ordinals.join(","))
+    }
+    fn to_json(&self) -> String {
+        let shared = self.shared().iter()
+            .map(|(c, p)| format!("{{\"item\":{},\"priority\":{}}}", json_string(&c.to_string()), p))
+            .collect::<Vec<_>>();
+        format!("{{\"index\":{},\"contents\":{},\"left\":{},\"right\":{},\"shared\":[{}]}}",
+            self.index + 1,
+            json_string(&self.pack.original),
+            self.compartment_json(&self.pack.left),
+            self.compartment_json(&self.pack.right),
+            shared.join(","))
+    }
+}
+
+impl Display for BackpackReport<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let join = |xs: Vec<i32>| xs.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
+        writeln!(f, "Backpack {}:", self.index + 1)?;
+        writeln!(f, "  From: {}", self.pack.original)?;
+        for (name, compartment) in [("Left", &self.pack.left), ("Right", &self.pack.right)] {
+            writeln!(f, "  {}:", name)?;
+            writeln!(f, "    From: {}", compartment.contents_str)?;
+            writeln!(f, "    Ordinals: {}", join(self.ordinals(&compartment.contents_str)))?;
         }
+        let shared = self.shared().iter()
+            .map(|(c, p)| format!("{} ({})", c, p))
+            .collect::<Vec<_>>();
+        write!(f, "  Common: {}", shared.join(", "))
     }
-    ret.dedup();
-    ret
 }
 
-impl Backpack {
-    fn common(&self) -> Vec<i32> {
-        intersection(self.left.contents.to_vec(), self.right.contents.to_vec())
+fn json_string(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
     }
-    fn debug_print(&self) {
-        println!("Backpack:");
-        println!("  From: {}", self.original);
-        println!("  Left:");
-        println!("    From: {}", self.left.contents_str);
-        println!("    Ordinals:");
-        print!("      ");
-        for p in &self.left.contents {
-            print!("{} ", p);
+    ret.push('"');
+    ret
+}
+
+// Which backpacks to report on: "all", a list of 1-based numbers such as
+// "1,5,7", or "common:<item>" for those with that item in both compartments.
+enum Selection {
+    All,
+    Indices(Vec<usize>),
+    Common(char)
+}
+
+impl FromStr for Selection {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || AocError::new(&format!("Bad selection: {:?}", s));
+        if s == "all" {
+            return Ok(Selection::All);
         }
-        println!("");
-        println!("  Right:");
-        println!("    From: {}", self.right.contents_str);
-        println!("    Ordinals:");
-        print!("      ");
-        for p in &self.right.contents {
-            print!("{} ", p);
+        if let Some(item) = s.strip_prefix("common:") {
+            let mut chars = item.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Selection::Common(c)),
+                _ => Err(bad()),
+            };
         }
-        println!("");
-        println!("  Common:");
-        print!("    ");
-        for p in self.common() {
-            print!("{} ", p);
+        s.split(',')
+            .map(|x| match x.trim().parse::<usize>() {
+                Ok(n) if n >= 1 => Ok(n - 1),
+                _ => Err(bad()),
+            })
+            .collect::<AocResult<Vec<_>>>()
+            .map(Selection::Indices)
+    }
+}
+
+impl Selection {
+    fn select(&self, packs: &[Backpack], table: &PriorityTable) -> AocResult<Vec<usize>> {
+        match self {
+            Selection::All => Ok((0..packs.len()).collect()),
+            Selection::Indices(indices) => indices.iter()
+                .map(|x| if *x < packs.len() {
+                    Ok(*x)
+                } else {
+                    Err(AocError::new(&format!("No backpack {}: there are {}", x + 1, packs.len())))
+                })
+                .collect(),
+            Selection::Common(item) => Ok((0..packs.len())
+                .filter(|idx| table.index(*item).is_some_and(|x| packs[*idx].common().contains(x)))
+                .collect()),
         }
-        println!("");
+    }
+}
+
+impl Compartment {
+    fn parse(s: &str, table: &PriorityTable) -> AocResult<Self> {
+        let contents = parse_chars(s, |c| table.index(c)
+            .ok_or_else(|| AocError::new(&format!("Bad character {:?}", c))))?;
+        let mut set = ItemSet::with_capacity(table.len());
+        for index in contents {
+            set.insert(index);
+        }
+        Ok(Compartment { contents_str: s.to_owned(), contents: set })
+    }
+}
+
+impl Backpack {
+    fn parse(s: &str, table: &PriorityTable) -> AocResult<Self> {
+        let half = s.chars().count() / 2;
+        let mid = s.char_indices().nth(half).map(|x| x.0).unwrap_or(s.len());
+        let (left, right) = s.split_at(mid);
+        let left_compartment = Compartment::parse(left, table)?;
+        let right_compartment = Compartment::parse(right, table).map_err(|e| e.at_column(half + 1))?;
+        let contents = &left_compartment.contents | &right_compartment.contents;
+        Ok(Backpack {original: s.to_owned(), contents, left: left_compartment, right: right_compartment})
     }
 }
 
 impl FromStr for Compartment {
-    type Err = io::Error;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let contents: Vec<i32> = s.chars().map(get_ascii_ordinal).collect::<io::Result<Vec<i32>>>()?;
-        Ok(Compartment { contents_str: s.to_owned(), contents: contents })
+        Compartment::parse(s, &PriorityTable::default())
     }
 }
 
 impl FromStr for Backpack {
-    type Err = io::Error;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (left, right) = s.split_at(s.len()/2);
-        let left_compartment = left.parse::<Compartment>()?;
-        let right_compartment = right.parse::<Compartment>()?;
-        let contents: Vec<i32> = s.chars().map(get_ascii_ordinal).collect::<io::Result<Vec<i32>>>()?;
-        Ok(Backpack {original: s.to_owned(), contents: contents, left: left_compartment, right: right_compartment})
+        Backpack::parse(s, &PriorityTable::default())
     }
 }
 
-fn get_ascii_ordinal(c: char) -> io::Result<i32> {
-    let ordinals = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
-    match ordinals.chars().position(|x| x == c) {
-        Some(x) => Ok(x as i32 + 1),
-        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Bad character")),
-    }
+fn describe_items(items: &ItemSet, table: &PriorityTable) -> String {
+    items.indices()
+        .map(|x| format!("{} ({})", table.item(x), table.priority(x)))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-//fn read_day3_input() -> io::Result<Vec<Backpack>> {
-//    read_data_records::<Backpack>("day3.txt")
-//}
+// How backpacks are put into groups: consecutive chunks, sliding windows, or
+// explicit lists of (1-based) backpack numbers read from a file, one group per
+// line. Written as "chunks:3", "windows:3" or "file:<name>".
+enum Grouping {
+    Chunks(usize),
+    Windows(usize),
+    Mapping(String)
+}
 
-fn read_day3_input() -> io::Result<Vec<Backpack>> {
-    let lines = read_data_lines("day3.txt")?;
-    lines.iter()
-        .map(|x| x.trim().to_owned())
-        .filter(|x| !x.is_empty())
-        .map(|x| x.parse::<Backpack>() )
+impl FromStr for Grouping {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || AocError::new("Bad grouping");
+        let (kind, arg) = s.split_once(':').ok_or_else(bad)?;
+        let size = || arg.parse::<usize>().ok().filter(|x| *x > 0).ok_or_else(bad);
+        match kind {
+            "chunks" => Ok(Grouping::Chunks(size()?)),
+            "windows" => Ok(Grouping::Windows(size()?)),
+            "file" => Ok(Grouping::Mapping(arg.to_owned())),
+            _ => Err(bad()),
+        }
+    }
+}
+
+fn read_group_mapping(filename: &str, num_packs: usize) -> AocResult<Vec<Vec<usize>>> {
+    let input = Input::open(Source::File(filename))?;
+    parse_numbered(input.lines()?, |x| x
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+        .map(|t| match t.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= num_packs => Ok(n - 1),
+            _ => Err(AocError::new(&format!("Bad backpack number: {}", t))),
+        })
+        .collect())
         .collect()
 }
 
+// The backpack indices in each group.
+fn make_groups(num_packs: usize, grouping: &Grouping) -> AocResult<Vec<Vec<usize>>> {
+    let incomplete = |n: usize| AocError::new(&format!("Incomplete group: {} backpacks left over", n));
+    match grouping {
+        Grouping::Chunks(size) => {
+            if !num_packs.is_multiple_of(*size) {
+                return Err(incomplete(num_packs % size));
+            }
+            Ok((0..num_packs / size).map(|i| (i * size..(i + 1) * size).collect()).collect())
+        },
+        Grouping::Windows(size) => {
+            if num_packs < *size {
+                return Err(incomplete(num_packs));
+            }
+            Ok((0..=(num_packs - size)).map(|i| (i..i + size).collect()).collect())
+        },
+        Grouping::Mapping(filename) => read_group_mapping(filename, num_packs),
+    }
+}
+
+fn group_badges(packs: &[Backpack], group: &[usize]) -> ItemSet {
+    group.iter()
+        .map(|idx| packs[*idx].contents.clone())
+        .reduce(|acc, item| &acc & &item)
+        .unwrap_or_default()
+}
+
+// The item found in both compartments of the most backpacks.
+fn most_common_misplaced(packs: &[Backpack], table: &PriorityTable) -> Option<(usize, usize)> {
+    let mut counts = vec![0; table.len()];
+    for pack in packs {
+        for index in pack.common().indices() {
+            counts[index] += 1;
+        }
+    }
+    counts.into_iter().enumerate()
+        .filter(|(_, count)| *count > 0)
+        .max_by_key(|(x, count)| (*count, -table.priority(*x)))
+}
+
+// DAY3_PRIORITIES names a file of "<item> <priority>" lines and DAY3_ALPHABET
+// lists items in priority order. Without either the puzzle's a-zA-Z is used.
+fn read_priority_table() -> AocResult<PriorityTable> {
+    if let Ok(filename) = std::env::var("DAY3_PRIORITIES") {
+        PriorityTable::read(&filename)
+    } else if let Ok(alphabet) = std::env::var("DAY3_ALPHABET") {
+        PriorityTable::from_alphabet(&alphabet)
+    } else {
+        Ok(PriorityTable::default())
+    }
+}
+
+// How day3_report prints: "text" or "json".
+enum ReportFormat {
+    Text,
+    Json
+}
+
+impl FromStr for ReportFormat {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(AocError::new(&format!("Unknown report format: {:?}", s))),
+        }
+    }
+}
+
+fn read_day3_input(table: &PriorityTable) -> AocResult<Vec<Backpack>> {
+    let input = Input::open(input_source("day3.txt"))?;
+    parse_numbered(input.lines()?, |x| Backpack::parse(x, table)).collect()
+}
+
 pub fn day3() {
-    let packs = read_day3_input().unwrap();
-    packs[0].debug_print();
-    let priority_sum: i32 = packs.iter().map(|x| x.common().iter().sum::<i32>()).sum();
+    let table = read_priority_table().unwrap();
+    let packs = read_day3_input(&table).unwrap();
+    println!("{}", BackpackReport { index: 0, pack: &packs[0], table: &table });
+    let priority_sum: i32 = packs.iter().map(|x| x.common().priority_sum(&table)).sum();
     println!("Backpack common priority sum: {}", priority_sum);
-    let group_sum: i32 = packs
-        .chunks(3)
-        .map(|chunk| chunk
-            .iter()
-            .map(|pack| pack.contents.to_vec())
-            .reduce(|acc, item| intersection(acc.to_vec(), item.to_vec())).unwrap()
-            .iter().sum::<i32>())
+    let groups = make_groups(packs.len(), &Grouping::Chunks(3)).unwrap();
+    let group_sum: i32 = groups.iter()
+        .map(|group| group_badges(&packs, group).priority_sum(&table))
         .sum();
     println!("Backpack group priority sum: {}", group_sum);
-}
\ No newline at end of file
+}
+
+// Set DAY3_GROUPING to choose how backpacks are grouped, e.g. "windows:3" or
+// "file:day3_groups_example.txt". Defaults to "chunks:3".
+pub fn day3_groups() {
+    let table = read_priority_table().unwrap();
+    let packs = read_day3_input(&table).unwrap();
+    let grouping = env_parse::<Grouping>("DAY3_GROUPING", "chunks:3").unwrap();
+    let groups = make_groups(packs.len(), &grouping).unwrap();
+    let mut unusual = Vec::new();
+    for (idx, group) in groups.iter().enumerate() {
+        let badges = group_badges(&packs, group);
+        let members = group.iter().map(|x| (x + 1).to_string()).collect::<Vec<_>>().join(", ");
+        println!("Group {} (backpacks {}): badges: {}", idx + 1, members, describe_items(&badges, &table));
+        if badges.len() != 1 {
+            unusual.push((idx, badges));
+        }
+    }
+    println!("Number of groups: {}", groups.len());
+    println!("Groups without exactly one badge: {}", unusual.len());
+    for (idx, badges) in unusual {
+        println!("  Group {}: {} shared items: {}", idx + 1, badges.len(), describe_items(&badges, &table));
+    }
+    if let Some((index, count)) = most_common_misplaced(&packs, &table) {
+        println!("Most common misplaced item: {} ({}) in {} backpacks", table.item(index), table.priority(index), count);
+    }
+}
+
+// DAY3_SELECT chooses the backpacks to describe (see Selection, defaults to
+// "all") and DAY3_FORMAT=json switches from text to json.
+pub fn day3_report() {
+    let table = read_priority_table().unwrap();
+    let packs = read_day3_input(&table).unwrap();
+    let selection = env_parse::<Selection>("DAY3_SELECT", "all").unwrap();
+    let format = env_parse::<ReportFormat>("DAY3_FORMAT", "text").unwrap();
+    let reports = selection.select(&packs, &table).unwrap().into_iter()
+        .map(|index| BackpackReport { index, pack: &packs[index], table: &table })
+        .collect::<Vec<_>>();
+    match format {
+        ReportFormat::Json => {
+            let lines = reports.iter().map(|x| format!("  {}", x.to_json())).collect::<Vec<_>>();
+            println!("[\n{}\n]", lines.join(",\n"));
+        },
+        ReportFormat::Text => for report in reports {
+            println!("{}", report);
+        },
+    }
+}
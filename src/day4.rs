@@ -1,9 +1,12 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use std::vec::Vec;
-use std::error::Error;
 use std::fmt::{self, Display, Formatter};
 
-use crate::utils::read_data_lines;
+use crate::interval::{Interval, IntervalSet};
+use crate::input::Input;
+use crate::utils::{env_parse, input_source, parse_record, AocError, AocResult, Pattern};
 
 struct ZoneRange {
     from: i32,
@@ -15,84 +18,98 @@ struct ZonePair {
     second: ZoneRange
 }
 
-#[derive(Debug)]
-struct ZoneError {
-    details: String
+// Strict parsing accepts exactly "a-b,c-d" with a <= b and c <= d. Lenient
+// parsing also allows whitespace around each number and, if normalise is
+// set, swaps the ends of reversed ranges rather than rejecting them.
+#[derive(Clone, Copy, Default)]
+struct ParseOptions {
+    lenient: bool,
+    normalise: bool
 }
 
-type ZoneResult<T> = Result<T, ZoneError>;
-
-impl From<std::io::Error> for ZoneError {
-    fn from(e: std::io::Error) -> Self {
-        ZoneError { details: e.to_string() }
+impl FromStr for ParseOptions {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(ParseOptions { lenient: false, normalise: false }),
+            "lenient" => Ok(ParseOptions { lenient: true, normalise: false }),
+            "normalise" => Ok(ParseOptions { lenient: true, normalise: true }),
+            _ => Err(AocError::new(&format!("Unknown parse mode: {:?}", s))),
+        }
     }
 }
 
-impl From<std::num::ParseIntError> for ZoneError {
-    fn from(e: std::num::ParseIntError) -> Self {
-        ZoneError { details: e.to_string() }
+fn parse_section(s: &str, options: ParseOptions) -> AocResult<i32> {
+    let token = if options.lenient { s.trim() } else { s };
+    if is_negative(token) {
+        return Err(AocError::new(&format!("Negative section: {:?}", s)));
+    }
+    if token.is_empty() || !token.chars().all(|c| c.is_ascii_digit()) {
+        return Err(AocError::new(&format!("Bad section: {:?}", s)));
     }
+    token.parse::<i32>().map_err(|_| AocError::new(&format!("Section out of range: {:?}", s)))
 }
 
-impl Display for ZoneError {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
-    }
+// A minus sign followed by a number, which the range pattern would
+// otherwise read as a missing first section.
+fn is_negative(s: &str) -> bool {
+    s.strip_prefix('-').is_some_and(|x| x.trim_start().starts_with(|c: char| c.is_ascii_digit()))
 }
 
-impl Error for ZoneError {
-    fn description(&self) -> &str {
-        &self.details
+const ZONE_RANGE: Pattern = Pattern("{from}-{to}");
+const ZONE_PAIR: Pattern = Pattern("{first},{second}");
+
+impl ZoneRange {
+    fn parse(s: &str, options: ParseOptions) -> AocResult<Self> {
+        let token = if options.lenient { s.trim_start() } else { s };
+        if is_negative(token) {
+            return Err(AocError::new(&format!("Negative section: {:?}", s)).at_column(s.len() - token.len() + 1));
+        }
+        let fields = ZONE_RANGE.captures(s)?;
+        let left = fields.with(0, |x| parse_section(x, options))?;
+        let right = fields.with(1, |x| parse_section(x, options))?;
+        if left <= right {
+            Ok(ZoneRange{from: left, to: right})
+        } else if options.normalise {
+            Ok(ZoneRange{from: right, to: left})
+        } else {
+            Err(AocError::new(&format!("Reversed range: {:?}", s)))
+        }
     }
 }
 
-impl ZoneError {
-    fn new(msg: &str) -> Self {
-        Self{details: msg.to_string()}
+impl ZonePair {
+    fn parse(s: &str, options: ParseOptions) -> AocResult<Self> {
+        let fields = ZONE_PAIR.captures(s)?;
+        let left = fields.with(0, |x| ZoneRange::parse(x, options))?;
+        let right = fields.with(1, |x| ZoneRange::parse(x, options))?;
+        Ok(ZonePair{first: left, second: right})
     }
 }
 
 impl FromStr for ZoneRange {
-    type Err = ZoneError;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let tokens = s.split('-').collect::<Vec<&str>>();
-        if tokens.len() != 2 {
-            return Err(ZoneError::new("Bad range"));
-        }
-        let left = tokens[0].parse::<i32>()?;
-        let right = tokens[1].parse::<i32>()?;
-        Ok(ZoneRange{from: left, to: right})
+        ZoneRange::parse(s, ParseOptions::default())
     }
 }
 
 impl FromStr for ZonePair {
-    type Err = ZoneError;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let tokens = s.split(',').collect::<Vec<&str>>();
-        if tokens.len() != 2 {
-            return Err(ZoneError::new("Bad pair"));
-        }
-        let left = tokens[0].parse::<ZoneRange>()?;
-        let right = tokens[1].parse::<ZoneRange>()?;
-        Ok(ZonePair{first: left, second: right})
+        ZonePair::parse(s, ParseOptions::default())
     }
 }
 
 impl ZoneRange {
-    fn inside(&self, point: i32) -> bool {
-        self.from <= point && point <= self.to
-    }
-    fn starts_inside(&self, that: &ZoneRange) -> bool {
-        that.inside(self.from)
-    }
-    fn ends_inside(&self, that: &ZoneRange) -> bool {
-        that.inside(self.to)
+    fn interval(&self) -> Interval<i32> {
+        Interval::Closed(self.from, self.to)
     }
     fn contains(&self, that: &ZoneRange) -> bool {
-        that.starts_inside(self) && that.ends_inside(self)
+        self.interval().contains(&that.interval())
     }
     fn intersects(&self, that: &ZoneRange) -> bool {
-        self.starts_inside(that) || self.ends_inside(that) || self.contains(that)
+        self.interval().intersects(&that.interval())
     }
 }
 
@@ -117,21 +134,151 @@ impl Display for ZonePair {
     }
 }
 
-fn read_day4_input() -> ZoneResult<Vec<ZonePair>> {
-    let lines = read_data_lines("day4.txt")?;
-    lines.iter()
-        .map(|x| x.trim().to_owned())
-        .filter(|x| !x.is_empty())
-        .map(|x| x.parse::<ZonePair>() )
-        .collect()
+// Counts of values added at positions 0..n, with prefix sums in O(log n).
+struct Fenwick(Vec<usize>);
+
+impl Fenwick {
+    fn new(n: usize) -> Self {
+        Fenwick(vec![0; n + 1])
+    }
+    fn add(&mut self, position: usize, count: usize) {
+        let mut i = position + 1;
+        while i < self.0.len() {
+            self.0[i] += count;
+            i += i & i.wrapping_neg();
+        }
+    }
+    // The total added at positions before the given one.
+    fn before(&self, position: usize) -> usize {
+        let mut i = position;
+        let mut ret = 0;
+        while i > 0 {
+            ret += self.0[i];
+            i -= i & i.wrapping_neg();
+        }
+        ret
+    }
+}
+
+// Counts the pairs (i, j) where assignment i contains an assignment j from a
+// different pair, and lists the first few of them. Assignment 2n is the first
+// elf of pair n and 2n+1 the second. Runs in O(n log n) plus the number
+// listed, however many containments there are.
+fn cross_pair_containments(input: &[ZonePair], limit: usize) -> (usize, Vec<(usize, usize)>) {
+    let ranges = input.iter()
+        .flat_map(|x| [&x.first, &x.second])
+        .collect::<Vec<_>>();
+    let mut order = (0..ranges.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| (ranges[*i].from, Reverse(ranges[*i].to)));
+    let mut ends = ranges.iter().map(|x| x.to).collect::<Vec<_>>();
+    ends.sort();
+    ends.dedup();
+    // everything seen so far starts at or before the current range, so it
+    // contains the current range if it ends at or after it
+    let mut seen = Fenwick::new(ends.len());
+    let mut listed: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    let mut found = Vec::new();
+    let mut count = 0;
+    for group in order.chunk_by(|a, b| (ranges[*a].from, ranges[*a].to) == (ranges[*b].from, ranges[*b].to)) {
+        let to = ranges[group[0]].to;
+        let end = ends.binary_search(&to).unwrap();
+        let containers = seen.before(ends.len()) - seen.before(end);
+        // identical ranges contain each other
+        count += group.len() * (containers + group.len() - 1);
+        seen.add(end, group.len());
+        if found.len() < limit {
+            let earlier = listed.range(to..).flat_map(|(_, x)| x.iter());
+            let candidates = earlier.chain(group.iter())
+                .flat_map(|i| group.iter().map(move |j| (*i, *j)))
+                .filter(|(i, j)| i != j && i / 2 != j / 2);
+            found.extend(candidates.take(limit - found.len()));
+            listed.entry(to).or_default().extend(group);
+        }
+    }
+    // take away containments within a pair
+    for pair in input {
+        count -= pair.first.contains(&pair.second) as usize + pair.second.contains(&pair.first) as usize;
+    }
+    (count, found)
+}
+
+// Picks as few ranges as possible that between them cover every section
+// covered by any range: greedily take, from the first uncovered section, the
+// range that starts at or before it and reaches furthest.
+fn minimal_cover(ranges: &[&ZoneRange]) -> Vec<usize> {
+    let mut order = (0..ranges.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| ranges[*i].from);
+    let mut chosen = Vec::new();
+    let mut next = 0;
+    let mut covered_to = i32::MIN;
+    while next < order.len() {
+        // skip ranges that add nothing
+        if ranges[order[next]].to <= covered_to {
+            next += 1;
+            continue;
+        }
+        let start = if ranges[order[next]].from > covered_to { ranges[order[next]].from } else { covered_to + 1 };
+        let mut best = order[next];
+        while next < order.len() && ranges[order[next]].from <= start {
+            if ranges[order[next]].to > ranges[best].to {
+                best = order[next];
+            }
+            next += 1;
+        }
+        chosen.push(best);
+        covered_to = ranges[best].to;
+    }
+    chosen
+}
+
+// Trims each chosen range so that it starts after the one before it ends.
+fn disjoint_assignment(ranges: &[&ZoneRange], chosen: &[usize]) -> Vec<ZoneRange> {
+    let mut ret: Vec<ZoneRange> = Vec::new();
+    for idx in chosen {
+        let range = ranges[*idx];
+        let from = match ret.last() {
+            Some(last) => range.from.max(last.to + 1),
+            None => range.from,
+        };
+        ret.push(ZoneRange { from, to: range.to });
+    }
+    ret
+}
+
+// Parse a line with the given options. Lenient parsing ignores whitespace
+// around the line, keeping error columns relative to the whole line.
+fn parse_day4_line(line: &str, options: ParseOptions) -> AocResult<ZonePair> {
+    if options.lenient {
+        parse_record(line, |x| ZonePair::parse(x, options))
+    } else {
+        ZonePair::parse(line, options)
+    }
+}
+
+// DAY4_PARSE selects "strict" (the default), "lenient" or "normalise" parsing.
+fn read_parse_options() -> AocResult<ParseOptions> {
+    env_parse::<ParseOptions>("DAY4_PARSE", "strict")
+}
+
+// Every non-blank line parsed with the given options. Unlike
+// parse_numbered, lines are only trimmed in lenient mode.
+fn parse_day4_lines<'a, I>(lines: I, options: ParseOptions) -> impl Iterator<Item = AocResult<ZonePair>> + use<'a, I>
+where I: Iterator<Item = (usize, &'a str)> {
+    lines.filter(|(_, x)| !x.trim().is_empty())
+        .map(move |(line_no, x)| parse_day4_line(x, options).map_err(|e| e.at_line(line_no)))
 }
 
-fn number_of_fully_overlapping_pairs() -> ZoneResult<i32> {
+fn read_day4_input() -> AocResult<Vec<ZonePair>> {
+    let input = Input::open(input_source("day4.txt"))?;
+    parse_day4_lines(input.lines()?, read_parse_options()?).collect()
+}
+
+fn number_of_fully_overlapping_pairs() -> AocResult<i32> {
     let input = read_day4_input()?;
     Ok(input.iter().filter(|x| x.fully_overlapping()).count() as i32)
 }
 
-fn number_of_intersecting_pairs() -> ZoneResult<i32> {
+fn number_of_intersecting_pairs() -> AocResult<i32> {
     let input = read_day4_input()?;
     Ok(input.iter().filter(|x| x.intersecting()).count() as i32)
 }
@@ -141,4 +288,91 @@ pub fn day4() {
     println!("Number of fully overlapping pairs: {}", num);
     let num2 = number_of_intersecting_pairs().unwrap();
     println!("Number of intersecting pairs: {}", num2);
-}
\ No newline at end of file
+}
+
+pub fn day4_coverage() {
+    let input = read_day4_input().unwrap();
+    if input.is_empty() {
+        println!("No assignments");
+        return;
+    }
+    let ranges = input.iter()
+        .flat_map(|x| [x.first.interval(), x.second.interval()])
+        .collect::<Vec<_>>();
+    let covered = IntervalSet::from_intervals(&ranges);
+    let from = input.iter().flat_map(|x| [x.first.from, x.second.from]).min().unwrap();
+    let to = input.iter().flat_map(|x| [x.first.to, x.second.to]).max().unwrap();
+    let bounds = Interval::Closed(from, to);
+    let uncovered = covered.gaps(&bounds);
+    let crowded = IntervalSet::depth_at_least(&ranges, 3);
+    println!("Sections assigned: {}", bounds);
+    println!("Sections covered: {}", covered.len());
+    if uncovered.is_empty() {
+        println!("Sections covered by nobody: none");
+    } else {
+        println!("Sections covered by nobody: {} {}", uncovered.len(), uncovered);
+    }
+    println!("Sections covered by more than two elves: {} {}", crowded.len(), crowded);
+    let mut shared = 0;
+    let mut exclusive = 0;
+    for pair in &input {
+        let first = IntervalSet::from_intervals([&pair.first.interval()]);
+        let second = IntervalSet::from_intervals([&pair.second.interval()]);
+        shared += first.intersection(&second).len();
+        exclusive += first.union(&second).difference(&first.intersection(&second)).len();
+    }
+    println!("Sections shared within pairs: {}", shared);
+    println!("Sections covered by only one elf of a pair: {}", exclusive);
+}
+
+pub fn day4_sweep() {
+    let input = read_day4_input().unwrap();
+    let ranges = input.iter()
+        .flat_map(|x| [x.first.interval(), x.second.interval()])
+        .collect::<Vec<_>>();
+    let (depth, busiest) = IntervalSet::max_depth(&ranges);
+    println!("Maximum overlap depth: {}", depth);
+    println!("Sections with the most elves assigned: {}", busiest);
+    let describe = |i: usize| {
+        let pair = &input[i / 2];
+        format!("pair {} elf {} ({})", i / 2 + 1, i % 2 + 1, if i.is_multiple_of(2) { &pair.first } else { &pair.second })
+    };
+    let (count, found) = cross_pair_containments(&input, 10);
+    for (i, j) in found {
+        println!("  {} contains {}", describe(i), describe(j));
+    }
+    println!("Number of cross-pair containments: {}", count);
+}
+
+pub fn day4_validate() {
+    let input = Input::open(input_source("day4.txt")).unwrap();
+    let options = read_parse_options().unwrap();
+    let mut checked = 0;
+    let mut bad = 0;
+    for result in parse_day4_lines(input.lines().unwrap(), options) {
+        checked += 1;
+        if let Err(e) = result {
+            println!("{}", e);
+            bad += 1;
+        }
+    }
+    println!("Assignments checked: {}", checked);
+    println!("Malformed assignments: {}", bad);
+}
+
+pub fn day4_optimise() {
+    let input = read_day4_input().unwrap();
+    let ranges = input.iter()
+        .flat_map(|x| [&x.first, &x.second])
+        .collect::<Vec<_>>();
+    let chosen = minimal_cover(&ranges);
+    let assignment = disjoint_assignment(&ranges, &chosen);
+    println!("Elves needed: {} of {}", chosen.len(), ranges.len());
+    let mut assignment = assignment.into_iter();
+    while let Some(first) = assignment.next() {
+        match assignment.next() {
+            Some(second) => println!("{}", ZonePair { first, second }),
+            None => println!("{}", first),
+        }
+    }
+}
@@ -1,36 +1,36 @@
 use std::str::FromStr;
 use std::vec::Vec;
-use crate::utils::{read_data_lines, AocError, AocResult};
+use crate::utils::{input_source, read_header_and_records, AocError, AocResult, Pattern};
 
+#[derive(Clone)]
 struct Column {
     stack: Vec<char>
 }
 
+#[derive(Clone)]
 struct Crates {
     stacks: Vec<Column>
 }
 
 impl Crates {
-    fn make() -> Crates {
-        Crates { stacks: vec![
-            Column { stack: vec!['W', 'D', 'G', 'B', 'H', 'R', 'V'] },
-            Column { stack: vec!['J', 'N', 'G', 'C', 'R', 'F' ] },
-            Column { stack: vec!['L', 'S', 'F', 'H', 'D', 'N', 'J'] },
-            Column { stack: vec!['J', 'D', 'S', 'V'] },
-            Column { stack: vec!['S', 'H', 'D', 'R', 'Q', 'W', 'N', 'V'] },
-            Column { stack: vec!['P', 'G', 'H', 'C', 'M'] },
-            Column { stack: vec!['F', 'J', 'B', 'G', 'L', 'Z', 'H', 'C'] },
-            Column { stack: vec!['S', 'J', 'R'] },
-            Column { stack: vec!['L', 'G', 'S', 'R', 'B', 'N', 'V', 'M'] }
-        ] }
+    // The drawing of the starting stacks, with the stack numbers on its last
+    // line. Stack i's crates are in column 4i + 1, as in "[A] [B]".
+    fn parse(drawing: &[String]) -> AocResult<Crates> {
+        let (numbers, rows) = drawing.split_last().ok_or(AocError::new("No drawing"))?;
+        let num_stacks = numbers.split_whitespace().count();
+        let mut stacks = (0..num_stacks).map(|_| Column { stack: Vec::new() }).collect::<Vec<_>>();
+        for (line, row) in rows.iter().enumerate().rev() {
+            let row = row.chars().collect::<Vec<_>>();
+            for (i, column) in stacks.iter_mut().enumerate() {
+                match &row[(4 * i).min(row.len())..(4 * i + 3).min(row.len())] {
+                    ['[', c, ']'] => column.stack.push(*c),
+                    cell if cell.iter().all(|c| *c == ' ') => {},
+                    _ => return Err(AocError::new("Bad crate").at_column(4 * i + 1).at_line(line + 1)),
+                }
+            }
+        }
+        Ok(Crates { stacks })
     }
-//    fn make_example() -> Crates {
-//        Crates { stacks: vec![
-//            Column { stack: vec!['Z', 'N' ] },
-//            Column { stack: vec!['M', 'C', 'D' ] },
-//            Column { stack: vec!['P'] },
-//        ] }
-//    }
 }
 
 impl Crates {
@@ -94,32 +94,26 @@ struct Move {
     destination: usize
 }
 
+const MOVE: Pattern = Pattern("move {amount} from {source} to {destination}");
+
 impl FromStr for Move {
     type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // move 2 from 2 to 7
-        let tokens = s.split(' ').collect::<Vec<&str>>();
-        if tokens.len() != 6 {
-            return Err(AocError::new("Bad move command"));
-        }
-        let amount = tokens[1].parse::<i32>()?;
-        let source = tokens[3].parse::<usize>()? - 1;
-        let destination = tokens[5].parse::<usize>()? - 1;
-        Ok(Move{amount: amount, source:source, destination: destination})
+        let fields = MOVE.captures(s)?;
+        let stack = |i: usize| fields.with(i, |x| match x.parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(n - 1),
+            _ => Err(AocError::new(&format!("bad stack: {:?}", x))),
+        });
+        Ok(Move{amount: fields.parse::<i32>(0)?, source: stack(1)?, destination: stack(2)?})
     }
 }
 
-fn read_day5_input() -> AocResult<Vec<Move>> {
-    let lines = read_data_lines("day5.txt")?;
-    lines.iter()
-        .filter(|x| x.contains("move"))
-        .map(|x| x.parse::<Move>() )
-        .collect()
+fn read_day5_input() -> AocResult<(Crates, Vec<Move>)> {
+    let (drawing, moves) = read_header_and_records::<Move>(input_source("day5.txt"))?;
+    Ok((Crates::parse(&drawing)?, moves))
 }
 
-fn do_day5(batched: bool) {
-    let program = read_day5_input().unwrap();
-    let mut crates = Crates::make();
+fn do_day5(mut crates: Crates, program: &[Move], batched: bool) {
     crates.print_state();
     let mut counter = 0;
     for cmd in program {
@@ -128,9 +122,9 @@ fn do_day5(batched: bool) {
         println!("{}. move {} from {} to {}", counter, cmd.amount, cmd.source+1, cmd.destination+1);
         println!("");
         if batched {
-            crates.execute_batched_move(&cmd);
+            crates.execute_batched_move(cmd);
         } else {
-            crates.execute(&cmd);
+            crates.execute(cmd);
         }
         crates.print_state();
     }
@@ -138,6 +132,7 @@ fn do_day5(batched: bool) {
 }
 
 pub fn day5() {
-    do_day5(false);
-    do_day5(true);
+    let (crates, program) = read_day5_input().unwrap();
+    do_day5(crates.clone(), &program, false);
+    do_day5(crates, &program, true);
 }
\ No newline at end of file
@@ -1,18 +1,236 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufReader, Read};
+use std::str::FromStr;
 
-use crate::utils::{read_data_text, AocResult, AocError};
+use crate::input::Input;
+use crate::utils::{env_parse, input_source, AocResult, AocError};
+
+// Decodes utf-8 chars from a byte stream, yielding each char together with
+// the number of bytes it took up.
+struct Utf8Chars<R: Read> {
+    bytes: io::Bytes<BufReader<R>>
+}
+
+impl<R: Read> Utf8Chars<R> {
+    fn new(reader: R) -> Self {
+        Utf8Chars { bytes: BufReader::new(reader).bytes() }
+    }
+    fn next_byte(&mut self) -> AocResult<u8> {
+        match self.bytes.next() {
+            Some(b) => Ok(b?),
+            None => Err(AocError::new("Truncated utf-8 sequence")),
+        }
+    }
+}
+
+impl<R: Read> Iterator for Utf8Chars<R> {
+    type Item = AocResult<(char, usize)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = match self.bytes.next()? {
+            Ok(b) => b,
+            Err(e) => return Some(Err(e.into())),
+        };
+        let len = match first.leading_ones() {
+            0 => 1,
+            2 => 2,
+            3 => 3,
+            4 => 4,
+            _ => return Some(Err(AocError::new("Invalid utf-8 sequence"))),
+        };
+        let mut buf = [first, 0, 0, 0];
+        for b in buf.iter_mut().take(len).skip(1) {
+            match self.next_byte() {
+                Ok(x) => *b = x,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(std::str::from_utf8(&buf[..len])
+            .map(|s| (s.chars().next().unwrap(), len))
+            .map_err(|_| AocError::new("Invalid utf-8 sequence")))
+    }
+}
+
+// Sliding window keeping a count of each char in it, so that checking
+// whether the window is a marker costs O(1) per char whatever its size.
+struct MarkerDetector {
+    size: usize,
+    window: VecDeque<char>,
+    counts: HashMap<char, usize>,
+    distinct: usize
+}
+
+impl MarkerDetector {
+    fn new(size: usize) -> AocResult<Self> {
+        if size == 0 {
+            return Err(AocError::new("Marker size must be at least 1"));
+        }
+        Ok(MarkerDetector { size, window: VecDeque::new(), counts: HashMap::new(), distinct: 0 })
+    }
+    // Add a char, returning true if the window is now a marker.
+    fn push(&mut self, c: char) -> bool {
+        if self.window.len() == self.size {
+            let old = self.window.pop_front().unwrap();
+            let count = self.counts.get_mut(&old).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&old);
+                self.distinct -= 1;
+            }
+        }
+        self.window.push_back(c);
+        let count = self.counts.entry(c).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            self.distinct += 1;
+        }
+        self.distinct == self.size
+    }
+    fn reset(&mut self) {
+        self.window.clear();
+        self.counts.clear();
+        self.distinct = 0;
+    }
+    fn contents(&self) -> String {
+        self.window.iter().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Marker {
+    size: usize,
+    chars: String,
+    // offsets just past the end of the marker
+    char_offset: usize,
+    byte_offset: usize
+}
+
+// Scans a stream for markers of each of the given sizes in a single pass,
+// yielding every window that is a marker in stream order.
+struct MarkerScanner<R: Read> {
+    chars: Utf8Chars<R>,
+    detectors: Vec<MarkerDetector>,
+    pending: VecDeque<Marker>,
+    char_offset: usize,
+    byte_offset: usize
+}
+
+impl<R: Read> MarkerScanner<R> {
+    fn new(reader: R, sizes: &[usize]) -> AocResult<Self> {
+        Ok(MarkerScanner {
+            chars: Utf8Chars::new(reader),
+            detectors: sizes.iter().map(|x| MarkerDetector::new(*x)).collect::<AocResult<_>>()?,
+            pending: VecDeque::new(),
+            char_offset: 0,
+            byte_offset: 0
+        })
+    }
+}
+
+impl<R: Read> Iterator for MarkerScanner<R> {
+    type Item = AocResult<Marker>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let (c, len) = match self.chars.next()? {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            self.char_offset += 1;
+            self.byte_offset += len;
+            for detector in &mut self.detectors {
+                if detector.push(c) {
+                    self.pending.push_back(Marker {
+                        size: detector.size,
+                        chars: detector.contents(),
+                        char_offset: self.char_offset,
+                        byte_offset: self.byte_offset
+                    });
+                }
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+// A message: the marker that started it and everything up to the next
+// marker. Frames whose payload is shorter than the decoder's minimum are
+// flagged as corrupt, since markers that close together are more likely
+// noise than real message boundaries.
+struct Frame {
+    offset: usize,
+    marker: String,
+    payload: String,
+    corrupt: bool
+}
+
+// Splits a datastream into frames at each start-of-message marker. Data
+// before the first marker is discarded, and markers never overlap.
+struct FrameDecoder<R: Read> {
+    chars: Utf8Chars<R>,
+    detector: MarkerDetector,
+    min_payload: usize,
+    current: Option<(usize, String)>,
+    buffer: String,
+    char_offset: usize,
+    done: bool
+}
+
+impl<R: Read> FrameDecoder<R> {
+    fn new(reader: R, marker_size: usize, min_payload: usize) -> AocResult<Self> {
+        Ok(FrameDecoder {
+            chars: Utf8Chars::new(reader),
+            detector: MarkerDetector::new(marker_size)?,
+            min_payload,
+            current: None,
+            buffer: String::new(),
+            char_offset: 0,
+            done: false
+        })
+    }
+    fn make_frame(&self, (offset, marker): (usize, String), payload: String) -> Frame {
+        let corrupt = payload.chars().count() < self.min_payload;
+        Frame { offset, marker, payload, corrupt }
+    }
+}
+
+impl<R: Read> Iterator for FrameDecoder<R> {
+    type Item = AocResult<Frame>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let c = match self.chars.next() {
+                Some(Ok((c, _))) => c,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.done = true;
+                    let current = self.current.take()?;
+                    let payload = std::mem::take(&mut self.buffer);
+                    return Some(Ok(self.make_frame(current, payload)));
+                },
+            };
+            self.char_offset += 1;
+            self.buffer.push(c);
+            if !self.detector.push(c) {
+                continue;
+            }
+            let marker = self.detector.contents();
+            self.detector.reset();
+            let payload_len = self.buffer.len() - marker.len();
+            self.buffer.truncate(payload_len);
+            let payload = std::mem::take(&mut self.buffer);
+            let previous = self.current.replace((self.char_offset, marker));
+            if let Some(previous) = previous {
+                return Some(Ok(self.make_frame(previous, payload)));
+            }
+        }
+        None
+    }
+}
 
 pub fn find_marker(marker_size: usize) -> AocResult<(usize, String)> {
-    let data = read_data_text("day6.txt")?;
-    let data_chars = data.chars().collect::<Vec<_>>();
-    let pair = data_chars
-        .windows(marker_size)
-        .enumerate()
-        .find(|(_, w)| w.iter()
-            .collect::<HashSet<_>>().len() == marker_size)
-        .ok_or(AocError::new("no matching code"))?;
-    let chars = pair.1.iter().collect::<String>();
-    Ok((pair.0 + marker_size, chars))
+    let input = Input::open(input_source("day6.txt"))?;
+    let marker = MarkerScanner::new(input.bytes(), &[marker_size])?
+        .next()
+        .ok_or(AocError::new("no matching code"))??;
+    Ok((marker.char_offset, marker.chars))
 }
 
 pub fn day6() {
@@ -22,4 +240,91 @@ pub fn day6() {
     let (idx2, chrs2) = find_marker(14).unwrap();
     println!("Second code: {}", chrs2);
     println!("Index past end of second code: {}", idx2);
-}
\ No newline at end of file
+}
+
+// Marker sizes to search for at once, such as "4,14,20".
+struct MarkerSizes(Vec<usize>);
+
+impl FromStr for MarkerSizes {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || AocError::new(&format!("Bad marker sizes: {:?}", s));
+        let sizes = s.split(',')
+            .map(|x| x.trim().parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(bad))
+            .collect::<AocResult<Vec<_>>>()?;
+        if sizes.iter().enumerate().any(|(i, x)| sizes[..i].contains(x)) {
+            return Err(bad());
+        }
+        Ok(MarkerSizes(sizes))
+    }
+}
+
+// How much of each size's markers to print: "summary" gives the count and
+// the first and last, "all" lists every marker as it is found.
+#[derive(PartialEq, Eq)]
+enum MarkerReport {
+    Summary,
+    All
+}
+
+impl FromStr for MarkerReport {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "summary" => Ok(MarkerReport::Summary),
+            "all" => Ok(MarkerReport::All),
+            _ => Err(AocError::new(&format!("Unknown marker report: {:?}", s))),
+        }
+    }
+}
+
+// DAY6_SIZES chooses the marker sizes and DAY6_REPORT how they are printed.
+pub fn day6_markers() {
+    let MarkerSizes(sizes) = env_parse::<MarkerSizes>("DAY6_SIZES", "4,14").unwrap();
+    let report = env_parse::<MarkerReport>("DAY6_REPORT", "summary").unwrap();
+    // count, first and last of each size
+    let mut found: Vec<(usize, Option<Marker>, Option<Marker>)> = vec![(0, None, None); sizes.len()];
+    let input = Input::open(input_source("day6.txt")).unwrap();
+    for marker in MarkerScanner::new(input.bytes(), &sizes).unwrap() {
+        let marker = marker.unwrap();
+        if report == MarkerReport::All {
+            println!("Size {}: {:?} ending at char {} (byte {})", marker.size, marker.chars, marker.char_offset, marker.byte_offset);
+        }
+        let (count, first, last) = &mut found[sizes.iter().position(|x| *x == marker.size).unwrap()];
+        *count += 1;
+        if first.is_none() {
+            *first = Some(marker.clone());
+        }
+        *last = Some(marker);
+    }
+    for (size, (count, first, last)) in sizes.iter().zip(found.iter()) {
+        println!("Markers of size {}: {}", size, count);
+        if let (Some(first), Some(last)) = (first, last) {
+            println!("  First: {:?} ending at char {} (byte {})", first.chars, first.char_offset, first.byte_offset);
+            println!("  Last: {:?} ending at char {} (byte {})", last.chars, last.char_offset, last.byte_offset);
+        }
+    }
+}
+
+// DAY6_MARKER_SIZE is the size of start-of-message markers and
+// DAY6_MIN_PAYLOAD the shortest payload that is not treated as corrupt.
+pub fn day6_frames() {
+    let marker_size = env_parse::<usize>("DAY6_MARKER_SIZE", "14").unwrap();
+    let min_payload = env_parse::<usize>("DAY6_MIN_PAYLOAD", "14").unwrap();
+    let input = Input::open(input_source("day6.txt")).unwrap();
+    let decoder = FrameDecoder::new(input.bytes(), marker_size, min_payload).unwrap();
+    let mut count = 0;
+    let mut corrupt = 0;
+    for frame in decoder {
+        let frame = frame.unwrap();
+        count += 1;
+        if frame.corrupt {
+            corrupt += 1;
+        }
+        println!("Frame at {}: marker {}, {} chars of payload{}",
+            frame.offset, frame.marker, frame.payload.chars().count(),
+            if frame.corrupt { " (corrupt)" } else { "" });
+    }
+    println!("Number of frames: {}", count);
+    println!("Number of corrupt frames: {}", corrupt);
+}
@@ -1,4 +1,4 @@
-use crate::utils::{read_data_lines, AocResult, AocError};
+use crate::utils::{read_data_records, AocResult, AocError, Pattern};
 use core::str::FromStr;
 
 enum Line {
@@ -8,24 +8,23 @@ enum Line {
     File(String, usize),
 }
 
+const CD: Pattern = Pattern("$ cd {directory}");
+const LS: Pattern = Pattern("$ ls");
+const DIRECTORY: Pattern = Pattern("dir {name}");
+const FILE: Pattern = Pattern("{size} {name}");
+
 impl FromStr for Line {
     type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let tokens = s.split(" ").collect::<Vec<_>>();
-        // todo: bad num tokens
-        match tokens[0] {
-            "$" => {
-                match tokens[1] {
-                    "cd" => Ok(Line::Cd(tokens[2].to_owned())),
-                    "ls" => Ok(Line::Ls),
-                    _ => Err(AocError::new("Unknown command"))
-                }
-            },
-            "dir" => Ok(Line::Directory(tokens[1].to_owned())),
-            _ => {
-                let sz = tokens[0].parse::<usize>()?;
-                Ok(Line::File(tokens[1].to_owned(), sz))
-            }
+        if s.starts_with("$ cd ") {
+            Ok(Line::Cd(CD.captures(s)?.get(0).to_owned()))
+        } else if s.starts_with('$') {
+            LS.captures(s).map(|_| Line::Ls)
+        } else if s.starts_with("dir ") {
+            Ok(Line::Directory(DIRECTORY.captures(s)?.get(0).to_owned()))
+        } else {
+            let fields = FILE.captures(s)?;
+            Ok(Line::File(fields.get(1).to_owned(), fields.parse::<usize>(0)?))
         }
     }
 }
@@ -150,8 +149,7 @@ impl Fs {
 
 
 fn read_day7_input() -> AocResult<Fs> {
-    let lines = read_data_lines("day7.txt")?;
-    let program = lines.iter().map(|x| x.parse::<Line>()).collect::<AocResult<Vec<_>>>()?;
+    let program = read_data_records::<Line>("day7.txt")?;
     let mut ret = Fs::new();
     ret.init(&program);
     Ok(ret)
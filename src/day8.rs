@@ -1,4 +1,5 @@
-use crate::utils::{read_data_lines, AocResult, AocError};
+use crate::input::Input;
+use crate::utils::{input_source, parse_grid, AocResult, AocError};
 
 use std::io;
 use std::io::BufRead;
@@ -6,7 +7,6 @@ use std::fs::File;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
 use std::io::Read;
-use core::num::ParseIntError;
 
 struct Cell {
     height: i32,
@@ -99,20 +99,15 @@ impl Heightmap {
 }
 
 fn read_day8_input() -> AocResult<Heightmap>{
-    let lines = read_data_lines("day8.txt")?;
-    let map = lines.iter()
-        .map(|x| x.chars()
-            .map(|y| y.to_string().parse::<i32>().map(|z| Cell {height: z, visible: false }))
-            .collect::<Result<Vec<Cell>, ParseIntError>>())
-        .collect::<Result<Vec<Vec<Cell>>, ParseIntError>>()?;
-    let height = map.len();
-    let width = map[0].len();
-    for row in &map {
-        if width != row.len() {
-            return Err(AocError::new("Inconsistent number of rows"));
-        }
+    let input = Input::open(input_source("day8.txt"))?;
+    let map = parse_grid(input.lines()?, |c| match c.to_digit(10) {
+        Some(height) => Ok(Cell {height: height as i32, visible: false }),
+        None => Err(AocError::new(&format!("Bad height: {:?}", c))),
+    })?;
+    if map.is_empty() {
+        return Err(AocError::new("Empty map"));
     }
-    Ok(Heightmap {width: width as i32, height: height as i32, values: map })
+    Ok(Heightmap {width: map[0].len() as i32, height: map.len() as i32, values: map })
 }
 
 pub fn day8() {
@@ -1,23 +1,42 @@
 
 
-use crate::utils::{read_data_lines, AocResult, AocError};
+use crate::gif::GifEncoder;
+use crate::input::{Input, Lines};
+use crate::interval::{Interval, IntervalSet};
+use crate::utils::{env_parse, input_source, parse_numbered, parse_record, AocResult, AocError, Pattern};
 
-use std::io;
-use std::io::BufRead;
+use std::io::{self, BufRead, Write};
 use std::fs::File;
-use std::error::Error;
-use std::fmt::{self, Display, Formatter};
-use std::io::Read;
-use core::num::ParseIntError;
+use std::thread;
+use std::time::Duration;
 use core::str::FromStr;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 #[derive(Clone, Copy)]
 enum Direction {
     Left,
     Right,
     Up,
-    Down
+    Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight
+}
+
+impl Direction {
+    fn step(&self) -> (i32, i32) {
+        match self {
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::UpLeft => (-1, -1),
+            Direction::UpRight => (1, -1),
+            Direction::DownLeft => (-1, 1),
+            Direction::DownRight => (1, 1)
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -28,113 +47,606 @@ struct Move {
 
 impl Move {
     fn displacement(&self) -> (i32, i32) {
-        match self.direction {
-            Direction::Left => (-self.amount, 0),
-            Direction::Right => (self.amount, 0),
-            Direction::Up => (0, -self.amount),
-            Direction::Down => (0, self.amount)
-        }
+        let (dx, dy) = self.direction.step();
+        (dx * self.amount, dy * self.amount)
     }
     fn direction(&self) -> (i32, i32) {
-        if (self.is_zero()) {
+        if self.is_zero() {
             return (0, 0);
         }
-        match self.direction {
-            Direction::Left => (-1, 0),
-            Direction::Right => (1, 0),
-            Direction::Up => (0, -1),
-            Direction::Down => (0, 1)
-        }
+        self.direction.step()
     }
     fn is_zero(&self) -> bool {
         self.amount == 0
     }
-    fn decremented(&self) -> Self {
-        Move {amount: self.amount - 1, direction: self.direction }
-    }
 }
 
+const MOVE: Pattern = Pattern("{direction} {distance}");
+
 impl FromStr for Move {
     type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let tokens = s.split(" ").collect::<Vec<_>>();
-        let direction = match tokens[0] {
+        let fields = MOVE.captures(s)?;
+        let direction = fields.with(0, |x| match x {
             "L" => Ok(Direction::Left),
             "R" => Ok(Direction::Right),
             "U" => Ok(Direction::Up),
             "D" => Ok(Direction::Down),
-            _ => Err(AocError::new("Unknown direction"))
-        }?;
-        let distance = tokens[1].parse::<i32>()?;
-        Ok(Move{amount: distance, direction: direction})
+            "UL" => Ok(Direction::UpLeft),
+            "UR" => Ok(Direction::UpRight),
+            "DL" => Ok(Direction::DownLeft),
+            "DR" => Ok(Direction::DownRight),
+            _ => Err(AocError::new(&format!("Unknown direction: {:?}", x)))
+        })?;
+        let distance = fields.parse::<u32>(1)?;
+        let distance = fields.with(1, |x| i32::try_from(distance)
+            .map_err(|_| AocError::new(&format!("distance too large: {:?}", x))))?;
+        Ok(Move{amount: distance, direction})
+    }
+}
+
+// The straight lines a run can follow: rows, columns, diagonals and
+// anti-diagonals. A cell is on one line of each, given by a key, and sits at
+// some position along it.
+const ROW: usize = 0;
+const COLUMN: usize = 1;
+const DIAGONAL: usize = 2;
+const ANTI_DIAGONAL: usize = 3;
+
+fn line_key(line: usize, (x, y): (i64, i64)) -> i64 {
+    match line {
+        ROW => y,
+        COLUMN => x,
+        DIAGONAL => x - y,
+        _ => x + y,
+    }
+}
+
+fn line_position(line: usize, (x, y): (i64, i64)) -> i64 {
+    if line == COLUMN { y } else { x }
+}
+
+fn line_cell(line: usize, key: i64, position: i64) -> (i64, i64) {
+    match line {
+        ROW => (position, key),
+        COLUMN => (key, position),
+        DIAGONAL => (position, position - key),
+        _ => (position, key - position),
+    }
+}
+
+// Set of visited cells, stored as the covered spans along rows, columns and
+// diagonals. Single cells go into their row, and taut runs into the line
+// they follow, so a run costs one span whatever its length and memory grows
+// with the number of moves rather than the number of cells. The same cell
+// may be covered on more than one line; the count only includes it once.
+struct Trail {
+    lines: [BTreeMap<i64, IntervalSet<i64>>; 4],
+    count: usize
+}
+
+impl Trail {
+    fn new() -> Self {
+        Trail { lines: Default::default(), count: 0 }
+    }
+    fn insert(&mut self, (x, y): (i32, i32)) {
+        self.insert_span(ROW, y as i64, x as i64, x as i64);
+    }
+    // Insert the n cells after start in a straight line along step.
+    fn insert_run(&mut self, (x, y): (i32, i32), (dx, dy): (i32, i32), n: i32) {
+        if n <= 0 {
+            return;
+        }
+        let line = match (dx, dy) {
+            (_, 0) => ROW,
+            (0, _) => COLUMN,
+            _ if dx == dy => DIAGONAL,
+            _ => ANTI_DIAGONAL,
+        };
+        let first = (x as i64 + dx as i64, y as i64 + dy as i64);
+        let last = (x as i64 + dx as i64 * n as i64, y as i64 + dy as i64 * n as i64);
+        let (a, b) = (line_position(line, first), line_position(line, last));
+        self.insert_span(line, line_key(line, first), a.min(b), a.max(b));
+    }
+    // Add positions from..=to of a line, counting the cells that are neither
+    // on it already nor covered by a line crossing it.
+    fn insert_span(&mut self, line: usize, key: i64, from: i64, to: i64) {
+        let own = self.lines[line].get(&key);
+        let mut crossed = HashSet::new();
+        for other in (0..self.lines.len()).filter(|x| *x != line) {
+            // along this line the other line's key is slope * position + offset
+            let offset = line_key(other, line_cell(line, key, 0));
+            let slope = line_key(other, line_cell(line, key, 1)) - offset;
+            let (a, b) = (slope * from + offset, slope * to + offset);
+            for (other_key, spans) in self.lines[other].range(a.min(b)..=a.max(b)) {
+                if (other_key - offset) % slope != 0 {
+                    continue;
+                }
+                let position = (other_key - offset) / slope;
+                let cell = line_cell(line, key, position);
+                if spans.contains_point(line_position(other, cell)) && !own.is_some_and(|x| x.contains_point(position)) {
+                    crossed.insert(position);
+                }
+            }
+        }
+        let added = self.lines[line].entry(key).or_default().insert(&Interval::Closed(from, to));
+        self.count += added as usize - crossed.len();
+    }
+    fn len(&self) -> usize {
+        self.count
     }
 }
 
 struct Rope {
     segments: Vec<(i32, i32)>,
-    trail: HashSet<(i32, i32)>
+    trail: Trail,
+    knot_trails: Vec<Trail>
 }
 
 impl Rope {
     fn new(len: i32) -> Self {
-        let mut ret = Rope { segments: Vec::new(), trail: HashSet::new() };
-        for i in 0..len {
-            ret.segments.push((0, 0));
-        }
-        ret.trail.insert((0, 0));
+        Rope::new_at(len, (0, 0))
+    }
+    fn new_at(len: i32, start: (i32, i32)) -> Self {
+        let mut ret = Rope {
+            segments: vec![start; len.max(1) as usize],
+            trail: Trail::new(),
+            knot_trails: Vec::new()
+        };
+        ret.trail.insert(start);
         ret
     }
-    fn move_head_by_one(&mut self, direction: (i32, i32)) {
+    // Start recording the cells visited by every knot, not just the tail.
+    fn track_knots(&mut self) {
+        self.knot_trails = self.segments.iter()
+            .map(|p| {
+                let mut trail = Trail::new();
+                trail.insert(*p);
+                trail
+            })
+            .collect();
+    }
+    fn knot_visits(&self) -> Vec<usize> {
+        self.knot_trails.iter().map(Trail::len).collect()
+    }
+    fn tail(&self) -> (i32, i32) {
+        *self.segments.last().unwrap()
+    }
+    // Move the head one step and drag the rest of the rope after it. Returns
+    // true if every segment moved by exactly the same step, in which case
+    // further steps in that direction just translate the whole rope.
+    fn move_head_by_one(&mut self, direction: (i32, i32)) -> bool {
         let (dx, dy) = direction;
-        let (mut x, mut y) = self.segments[0];
-        x += dx;
-        y += dy;
-        self.segments[0] = (x, y);
-        self.reposition_tail(1);
-    }
-    fn reposition_tail(&mut self, idx: usize) {
-        if idx >= self.segments.len() {
-            return;
+        let (x, y) = self.segments[0];
+        self.segments[0] = (x + dx, y + dy);
+        if let Some(trail) = self.knot_trails.first_mut() {
+            trail.insert(self.segments[0]);
         }
+        let mut rigid = true;
+        for idx in 1..self.segments.len() {
+            let moved = self.reposition_segment(idx);
+            if moved != direction {
+                rigid = false;
+            }
+            if moved == (0, 0) {
+                break;
+            }
+            if let Some(trail) = self.knot_trails.get_mut(idx) {
+                trail.insert(self.segments[idx]);
+            }
+        }
+        self.trail.insert(self.tail());
+        rigid
+    }
+    fn reposition_segment(&mut self, idx: usize) -> (i32, i32) {
         let (x0, y0) = self.segments[idx];
         let (x1, y1) = self.segments[idx-1];
         let dx = x1 - x0;
         let dy = y1 - y0;
         if dx.abs() <= 1 && dy.abs() <= 1 {
-            return;
+            return (0, 0);
         }
         self.segments[idx] = (x0 + dx.signum(), y0 + dy.signum());
-        if idx == self.segments.len()-1 {
-            self.trail.insert(self.segments[idx]);
-        }
-        self.reposition_tail(idx+1);
+        (dx.signum(), dy.signum())
     }
     fn move_head(&mut self, mv: Move) {
-        if (mv.is_zero()) {
+        let (dx, dy) = mv.direction();
+        let mut remaining = mv.amount;
+        while remaining > 0 {
+            remaining -= 1;
+            if self.move_head_by_one((dx, dy)) {
+                break;
+            }
+        }
+        if remaining <= 0 {
             return;
         }
-        self.move_head_by_one(mv.direction());
-        self.move_head(mv.decremented());
+        // the rope is taut: move it in bulk for the rest of the run
+        self.trail.insert_run(self.tail(), (dx, dy), remaining);
+        for (idx, (x, y)) in self.segments.iter_mut().enumerate() {
+            if let Some(trail) = self.knot_trails.get_mut(idx) {
+                trail.insert_run((*x, *y), (dx, dy), remaining);
+            }
+            *x += dx * remaining;
+            *y += dy * remaining;
+        }
     }
 }
 
+// Several ropes moving on the same grid in lockstep: on each tick every rope
+// whose program has not finished takes one step. Returns the number of
+// collisions, counted as (tick, cell) pairs where knots of more than one rope
+// meet.
+fn run_ropes(ropes: &mut [Rope], programs: &[Vec<Move>]) -> usize {
+    let mut steps = programs.iter()
+        .map(|program| program.iter()
+            .flat_map(|mv| std::iter::repeat_n(mv.direction(), mv.amount as usize)))
+        .collect::<Vec<_>>();
+    let mut collisions = 0;
+    loop {
+        let mut moved = false;
+        for (rope, program) in ropes.iter_mut().zip(steps.iter_mut()) {
+            if let Some(step) = program.next() {
+                rope.move_head_by_one(step);
+                moved = true;
+            }
+        }
+        if !moved {
+            return collisions;
+        }
+        let mut occupied: HashMap<(i32, i32), (usize, bool)> = HashMap::new();
+        for (idx, rope) in ropes.iter().enumerate() {
+            for p in &rope.segments {
+                let entry = occupied.entry(*p).or_insert((idx, false));
+                if entry.0 != idx {
+                    entry.1 = true;
+                }
+            }
+        }
+        collisions += occupied.values().filter(|(_, collided)| *collided).count();
+    }
+}
+
+// A rope header line: "rope <length> <x>,<y>"
+struct RopeSpec {
+    len: i32,
+    start: (i32, i32)
+}
+
+const ROPE_SPEC: Pattern = Pattern("rope {length} {x},{y}");
+
+impl FromStr for RopeSpec {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = ROPE_SPEC.captures(s)?;
+        let len = fields.parse::<i32>(0)?;
+        fields.with(0, |x| match len {
+            1.. => Ok(()),
+            _ => Err(AocError::new(&format!("Rope length must be at least 1: {}", x))),
+        })?;
+        Ok(RopeSpec { len, start: (fields.parse::<i32>(1)?, fields.parse::<i32>(2)?) })
+    }
+}
+
+// Segment positions after every single step of the head, starting from the
+// initial position.
+fn trace(len: i32, moves: &[Move]) -> Vec<Vec<(i32, i32)>> {
+    let mut rope = Rope::new(len);
+    let mut frames = vec![rope.segments.clone()];
+    for mv in moves {
+        for _ in 0..mv.amount {
+            rope.move_head_by_one(mv.direction());
+            frames.push(rope.segments.clone());
+        }
+    }
+    frames
+}
+
+// Label segments the way the puzzle text does.
+fn segment_label(idx: usize, len: usize) -> char {
+    if idx == 0 {
+        'H'
+    } else if len == 2 {
+        'T'
+    } else {
+        std::char::from_digit(idx as u32, 10).unwrap_or('*')
+    }
+}
+
+struct Viewport {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    margin: i32
+}
+
+impl Viewport {
+    fn new(width: i32, height: i32) -> Self {
+        let margin = (width.min(height) / 4).max(1);
+        Viewport { x: -width / 2, y: -height / 2, width, height, margin }
+    }
+    // Scroll so that the point stays at least margin cells from the edge.
+    fn follow(&mut self, (px, py): (i32, i32)) {
+        if px < self.x + self.margin {
+            self.x = px - self.margin;
+        } else if px >= self.x + self.width - self.margin {
+            self.x = px - self.width + self.margin + 1;
+        }
+        if py < self.y + self.margin {
+            self.y = py - self.margin;
+        } else if py >= self.y + self.height - self.margin {
+            self.y = py - self.height + self.margin + 1;
+        }
+    }
+    fn render(&self, segments: &[(i32, i32)], trail: &HashSet<(i32, i32)>) -> String {
+        let mut ret = String::new();
+        for y in self.y..(self.y + self.height) {
+            for x in self.x..(self.x + self.width) {
+                let c = match segments.iter().position(|p| *p == (x, y)) {
+                    Some(idx) => segment_label(idx, segments.len()),
+                    None if (x, y) == (0, 0) => 's',
+                    None if trail.contains(&(x, y)) => '#',
+                    None => '.',
+                };
+                ret.push(c);
+            }
+            ret.push('\n');
+        }
+        ret
+    }
+}
+
+fn terminal_size() -> (i32, i32) {
+    let get = |name: &str, default: i32| std::env::var(name).ok()
+        .and_then(|x| x.parse::<i32>().ok())
+        .unwrap_or(default);
+    (get("COLUMNS", 80), get("LINES", 24) - 2)
+}
+
+fn animate(frames: &[Vec<(i32, i32)>], delay: Duration) -> io::Result<()> {
+    let (width, height) = terminal_size();
+    let mut viewport = Viewport::new(width, height);
+    let mut trail = HashSet::new();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for (step, segments) in frames.iter().enumerate() {
+        trail.insert(*segments.last().unwrap());
+        viewport.follow(segments[0]);
+        write!(out, "\x1b[H\x1b[2J{}", viewport.render(segments, &trail))?;
+        writeln!(out, "step {}/{}, tail positions: {}", step, frames.len() - 1, trail.len())?;
+        out.flush()?;
+        thread::sleep(delay);
+    }
+    Ok(())
+}
+
+const GIF_SCALE: usize = 3;
+const GIF_BACKGROUND: u8 = 0;
+const GIF_TRAIL: u8 = 1;
+const GIF_START: u8 = 2;
+const GIF_HEAD: u8 = 3;
+const GIF_KNOT: u8 = 4;
+const GIF_PALETTE: [[u8; 3]; 5] = [
+    [255, 255, 255],
+    [160, 160, 160],
+    [0, 160, 0],
+    [220, 0, 0],
+    [0, 0, 200],
+];
+
+// Write every step of the run as a frame of an animated gif. Each frame only
+// covers the cells that changed since the previous one.
+fn write_gif<W: Write>(out: W, frames: &[Vec<(i32, i32)>], delay_cs: u16) -> AocResult<W> {
+    let points = || frames.iter().flatten();
+    let min_x = points().map(|p| p.0).min().ok_or(AocError::new("No frames"))?;
+    let max_x = points().map(|p| p.0).max().unwrap();
+    let min_y = points().map(|p| p.1).min().unwrap();
+    let max_y = points().map(|p| p.1).max().unwrap();
+    let width = (max_x - min_x + 1) as usize;
+    let height = (max_y - min_y + 1) as usize;
+    if width * GIF_SCALE > u16::MAX as usize || height * GIF_SCALE > u16::MAX as usize {
+        return Err(AocError::new("Rope wanders too far for a gif"));
+    }
+    let index = |(x, y): (i32, i32)| (y - min_y) as usize * width + (x - min_x) as usize;
+    let mut base = vec![GIF_BACKGROUND; width * height];
+    base[index((0, 0))] = GIF_START;
+    let mut cells = base.clone();
+    let mut gif = GifEncoder::new(out, (width * GIF_SCALE) as u16, (height * GIF_SCALE) as u16, &GIF_PALETTE)?;
+    let mut previous: &[(i32, i32)] = &[];
+    for segments in frames {
+        let tail = *segments.last().unwrap();
+        if tail != (0, 0) {
+            base[index(tail)] = GIF_TRAIL;
+        }
+        for p in previous.iter().chain(segments.iter()) {
+            cells[index(*p)] = base[index(*p)];
+        }
+        for (idx, p) in segments.iter().enumerate().rev() {
+            cells[index(*p)] = if idx == 0 { GIF_HEAD } else { GIF_KNOT };
+        }
+        // the first frame paints the whole canvas
+        let dirty = || previous.iter().chain(segments.iter());
+        let (x0, x1, y0, y1) = if previous.is_empty() {
+            (0, width as i32, 0, height as i32)
+        } else {
+            (dirty().map(|p| p.0).min().unwrap() - min_x,
+             dirty().map(|p| p.0).max().unwrap() - min_x + 1,
+             dirty().map(|p| p.1).min().unwrap() - min_y,
+             dirty().map(|p| p.1).max().unwrap() - min_y + 1)
+        };
+        let (w, h) = ((x1 - x0) as usize, (y1 - y0) as usize);
+        let mut pixels = Vec::with_capacity(w * h * GIF_SCALE * GIF_SCALE);
+        for y in 0..(h * GIF_SCALE) {
+            let row = (y0 as usize + y / GIF_SCALE) * width;
+            for x in 0..(w * GIF_SCALE) {
+                pixels.push(cells[row + x0 as usize + x / GIF_SCALE]);
+            }
+        }
+        gif.frame(
+            (x0 as usize * GIF_SCALE) as u16, (y0 as usize * GIF_SCALE) as u16,
+            (w * GIF_SCALE) as u16, (h * GIF_SCALE) as u16,
+            &pixels, delay_cs)?;
+        previous = segments;
+    }
+    Ok(gif.finish()?)
+}
+
+// Each "rope" line starts a new rope. Moves before the first of them drive a
+// ten knot rope from the origin, so plain day 9 inputs are one rope.
+fn read_day9_multi_input() -> AocResult<Vec<(RopeSpec, Vec<Move>)>> {
+    let input = Input::open(input_source("day9.txt"))?;
+    let mut ret: Vec<(RopeSpec, Vec<Move>)> = Vec::new();
+    parse_numbered(input.lines()?, |line| {
+        if line.starts_with("rope") {
+            ret.push((line.parse::<RopeSpec>()?, Vec::new()));
+        } else {
+            if ret.is_empty() {
+                ret.push((RopeSpec { len: 10, start: (0, 0) }, Vec::new()));
+            }
+            ret.last_mut().unwrap().1.push(line.parse::<Move>()?);
+        }
+        Ok(())
+    }).collect::<AocResult<()>>()?;
+    Ok(ret)
+}
+
+// Parse moves one line at a time as they arrive, skipping blank lines.
+fn parse_moves<R: BufRead>(reader: R) -> impl Iterator<Item = AocResult<Move>> {
+    reader.lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(x) if x.trim().is_empty()))
+        .map(|(idx, line)| parse_record(&line?, |x| x.parse::<Move>()).map_err(|e| e.at_line(idx + 1)))
+}
+
+// The same, over lines borrowed from an input.
+fn parse_input_moves(lines: Lines<'_>) -> impl Iterator<Item = AocResult<Move>> + '_ {
+    parse_numbered(lines, |x| x.parse::<Move>())
+}
+
 fn read_day9_input() -> AocResult<Vec<Move>> {
-    let lines = read_data_lines("day9.txt")?;
-    lines.iter()
-        .map(|x| 
-            x.parse::<Move>()).collect::<AocResult<Vec<_>>>()
+    let input = Input::open(input_source("day9.txt"))?;
+    parse_input_moves(input.lines()?).collect()
 }
 
 pub fn day9() {
+    let input = Input::open(input_source("day9.txt")).unwrap();
     let mut rope = Rope::new(2);
+    let mut rope2: Rope = Rope::new(10);
+    for mv in parse_input_moves(input.lines().unwrap()) {
+        let mv = mv.unwrap();
+        rope.move_head(mv);
+        rope2.move_head(mv);
+    }
+    println!("Number of tail positions: {}", rope.trail.len());
+    println!("Number of tail positions in long rope: {}", rope2.trail.len());
+}
+
+pub fn day9_animate() {
     let moves = read_day9_input().unwrap();
+    animate(&trace(10, &moves), Duration::from_millis(30)).unwrap();
+}
+
+pub fn day9_gif() {
+    let moves = read_day9_input().unwrap();
+    let file = File::create("day9.gif").unwrap();
+    write_gif(io::BufWriter::new(file), &trace(10, &moves), 2).unwrap();
+    println!("Wrote day9.gif");
+}
+
+pub fn day9_knots() {
+    let moves = read_day9_input().unwrap();
+    let mut rope = Rope::new(10);
+    rope.track_knots();
     for mv in &moves {
         rope.move_head(*mv);
     }
-    println!("Number of tail positions: {}", rope.trail.len());
-    let mut rope2: Rope = Rope::new(10);
-    for mv in &moves {
-        rope2.move_head(*mv);
+    for (idx, visits) in rope.knot_visits().iter().enumerate() {
+        println!("Positions visited by knot {}: {}", segment_label(idx, rope.segments.len()), visits);
     }
+}
+
+pub fn day9_multi() {
+    let input = read_day9_multi_input().unwrap();
+    let mut ropes = input.iter()
+        .map(|(spec, _)| {
+            let mut rope = Rope::new_at(spec.len, spec.start);
+            rope.track_knots();
+            rope
+        })
+        .collect::<Vec<_>>();
+    let programs = input.into_iter().map(|(_, program)| program).collect::<Vec<_>>();
+    let collisions = run_ropes(&mut ropes, &programs);
+    for (idx, rope) in ropes.iter().enumerate() {
+        println!("Rope {}: tail positions: {}, knot positions: {:?}", idx, rope.trail.len(), rope.knot_visits());
+    }
+    println!("Number of collisions: {}", collisions);
+}
+
+// Follow moves from stdin as they arrive. Malformed lines are reported and
+// skipped. Set DAY9_REPORT_EVERY=n to print the running counts every n moves.
+pub fn day9_stream() {
+    let report_every = env_parse::<usize>("DAY9_REPORT_EVERY", "0").unwrap();
+    let mut rope = Rope::new(2);
+    let mut rope2 = Rope::new(10);
+    let mut count = 0;
+    for mv in parse_moves(io::stdin().lock()) {
+        match mv {
+            Ok(mv) => {
+                rope.move_head(mv);
+                rope2.move_head(mv);
+                count += 1;
+                if report_every > 0 && count % report_every == 0 {
+                    println!("After {} moves: tail positions: {}, long rope: {}", count, rope.trail.len(), rope2.trail.len());
+                }
+            },
+            Err(e) => eprintln!("Skipping bad move: {}", e),
+        }
+    }
+    println!("Number of moves: {}", count);
+    println!("Number of tail positions: {}", rope.trail.len());
     println!("Number of tail positions in long rope: {}", rope2.trail.len());
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Rng;
+
+    #[test]
+    fn trail_counts_each_cell_once() {
+        let steps = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (-1, -1), (1, -1), (-1, 1)];
+        let mut rng = Rng::new(9);
+        for _ in 0..50 {
+            let mut trail = Trail::new();
+            let mut cells = HashSet::new();
+            for _ in 0..40 {
+                let start = (rng.between(0, 20) as i32 - 10, rng.between(0, 20) as i32 - 10);
+                if rng.below(3) == 0 {
+                    trail.insert(start);
+                    cells.insert(start);
+                    continue;
+                }
+                let step = *rng.choose(&steps);
+                let n = rng.between(0, 12) as i32;
+                trail.insert_run(start, step, n);
+                cells.extend((1..=n).map(|i| (start.0 + step.0 * i, start.1 + step.1 * i)));
+                assert_eq!(trail.len(), cells.len());
+            }
+        }
+    }
+
+    #[test]
+    fn long_runs_are_single_spans() {
+        let mut trail = Trail::new();
+        trail.insert((0, 0));
+        trail.insert_run((0, 0), (1, 0), 1_000_000_000);
+        trail.insert_run((5, 5), (0, -1), 1_000_000_000);
+        trail.insert_run((-3, 3), (1, -1), 1_000_000_000);
+        // the column crosses the row at (5, 0), the anti-diagonal crosses
+        // the row at (0, 0) and the column at (5, -5)
+        assert_eq!(trail.len(), 3_000_000_001 - 3);
+        // the start cell and the row run share a span
+        assert_eq!(trail.lines.iter().flat_map(|x| x.values()).map(|x| x.iter().count()).sum::<usize>(), 3);
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::HashSet;
+use std::io::{self, BufWriter, Write};
+
+use crate::utils::{AocError, AocResult, Rng};
+
+// Random puzzle inputs for testing and benchmarking, written to stdout:
+//
+//     aoc2022 gen --day N [--size S] [--seed X] [--edge]
+//
+// Size is roughly the number of records (elves, rounds, moves...), or the
+// side of the forest for day 8. With --edge the input is built around the
+// cases solvers tend to get wrong: ties, empty stacks, extreme values and
+// near misses. The same options always give the same input.
+struct GenOptions {
+    day: u32,
+    size: usize,
+    seed: u64,
+    edge: bool
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: &str) -> AocResult<T> {
+    value.parse().map_err(|_| AocError::new(&format!("Bad value for {}: {:?}", option, value)))
+}
+
+impl GenOptions {
+    fn parse(args: &[String]) -> AocResult<GenOptions> {
+        let mut options = GenOptions { day: 0, size: 100, seed: 1, edge: false };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(AocError::new(&format!("Missing value for {}", arg)));
+            match arg.as_str() {
+                "--day" => options.day = number(arg, value()?)?,
+                "--size" => options.size = number(arg, value()?)?,
+                "--seed" => options.seed = number(arg, value()?)?,
+                "--edge" => options.edge = true,
+                _ => return Err(AocError::new(&format!("Unknown option: {}", arg))),
+            }
+        }
+        if !(1..=9).contains(&options.day) {
+            return Err(AocError::new("Expected --day 1 to 9"));
+        }
+        Ok(options)
+    }
+}
+
+const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+fn day1(out: &mut impl Write, rng: &mut Rng, size: usize, edge: bool) -> io::Result<()> {
+    let mut elves: Vec<Vec<u64>> = Vec::new();
+    if edge {
+        // a total beyond u64, then a three way tie for second place, then
+        // single item elves
+        elves.push(vec![u64::MAX, u64::MAX]);
+        elves.push(vec![u64::MAX - 1, 1]);
+        elves.push(vec![u64::MAX]);
+        elves.push(vec![u64::MAX / 2, u64::MAX / 2, 1]);
+        elves.push(vec![0]);
+        elves.push(vec![1]);
+    }
+    while elves.len() < size {
+        let count = rng.between(1, 15) as usize;
+        elves.push((0..count).map(|_| rng.between(1000, 60000)).collect());
+    }
+    elves.truncate(size.max(1));
+    for (i, elf) in elves.iter().enumerate() {
+        if i > 0 {
+            // runs of blank lines between elves are allowed
+            let blanks = if edge { rng.between(1, 3) } else { 1 };
+            for _ in 0..blanks {
+                writeln!(out)?;
+            }
+        }
+        for item in elf {
+            writeln!(out, "{}", item)?;
+        }
+    }
+    Ok(())
+}
+
+fn day2(out: &mut impl Write, rng: &mut Rng, size: usize, edge: bool) -> io::Result<()> {
+    for i in 0..size {
+        let (thee, me) = if edge {
+            // every combination in turn, then long runs of one
+            if i < 9 * 3 {
+                (i % 9 / 3, i % 3)
+            } else {
+                (i / 50 % 3, i / 150 % 3)
+            }
+        } else {
+            (rng.below(3) as usize, rng.below(3) as usize)
+        };
+        writeln!(out, "{} {}", (b'A' + thee as u8) as char, (b'X' + me as u8) as char)?;
+    }
+    Ok(())
+}
+
+// Each group of three shares exactly one badge, and each backpack has
+// exactly one item in both compartments. Every item other than the badge is
+// left out of at least one backpack in the group, so it cannot be a badge,
+// and each backpack's compartments draw their other items from disjoint
+// pools.
+fn day3(out: &mut impl Write, rng: &mut Rng, size: usize, edge: bool) -> io::Result<()> {
+    for _ in 0..size.div_ceil(3).max(1) {
+        let badge = *rng.choose(LETTERS);
+        let mut others = LETTERS.iter().copied().filter(|x| *x != badge).collect::<Vec<_>>();
+        rng.shuffle(&mut others);
+        let third = others.len() / 3;
+        for k in 0..3 {
+            let mut allowed = others.iter().enumerate()
+                .filter(|(i, _)| *i / third != k)
+                .map(|(_, x)| *x)
+                .collect::<Vec<_>>();
+            let common = if edge || rng.below(4) == 0 {
+                badge
+            } else {
+                allowed.remove(rng.below(allowed.len() as u64) as usize)
+            };
+            let (left_pool, right_pool) = allowed.split_at(allowed.len() / 2);
+            let half = if edge {
+                *rng.choose(&[1, 2, 24])
+            } else {
+                rng.between(2, 16) as usize
+            };
+            let mut left = vec![common];
+            let mut right = vec![common];
+            if common != badge {
+                left.push(badge);
+            }
+            while left.len() < half.max(right.len()) {
+                left.push(*rng.choose(left_pool));
+            }
+            while right.len() < left.len() {
+                right.push(*rng.choose(right_pool));
+            }
+            rng.shuffle(&mut left);
+            rng.shuffle(&mut right);
+            left.extend(right);
+            writeln!(out, "{}", String::from_utf8(left).unwrap())?;
+        }
+    }
+    Ok(())
+}
+
+fn day4(out: &mut impl Write, rng: &mut Rng, size: usize, edge: bool) -> io::Result<()> {
+    let range = |rng: &mut Rng| {
+        let from = rng.between(1, 99);
+        (from, rng.between(from, 99))
+    };
+    for i in 0..size {
+        let ((a, b), (c, d)) = if edge {
+            // identical, single section, touching, nested both ways,
+            // adjacent but disjoint, and the extremes
+            let x = rng.between(2, 97);
+            match i % 7 {
+                0 => ((x, x + 2), (x, x + 2)),
+                1 => ((x, x), (x, x)),
+                2 => ((x - 1, x), (x, x + 1)),
+                3 => ((x - 1, x + 1), (x, x)),
+                4 => ((x, x), (x - 1, x + 1)),
+                5 => ((x - 1, x), (x + 1, x + 2)),
+                _ => ((1, 99), (99, 99)),
+            }
+        } else {
+            (range(rng), range(rng))
+        };
+        writeln!(out, "{}-{},{}-{}", a, b, c, d)?;
+    }
+    Ok(())
+}
+
+// A drawing of nine stacks and a program in which every move only takes
+// crates that are there.
+fn day5(out: &mut impl Write, rng: &mut Rng, size: usize, edge: bool) -> io::Result<()> {
+    let num_stacks = 9;
+    let mut stacks = (0..num_stacks)
+        .map(|i| {
+            let height = if edge && i % 3 == 0 { 0 } else { rng.between(1, 8) };
+            (0..height).map(|_| (b'A' + rng.below(26) as u8) as char).collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if edge {
+        // one tall stack to shuffle between the empty ones
+        stacks[num_stacks - 1].extend((0..30).map(|i| (b'A' + (i % 26) as u8) as char));
+    }
+    let height = stacks.iter().map(|x| x.len()).max().unwrap_or(0);
+    for row in (0..height).rev() {
+        let cells = stacks.iter()
+            .map(|x| x.get(row).map_or("   ".to_owned(), |c| format!("[{}]", c)))
+            .collect::<Vec<_>>();
+        writeln!(out, "{}", cells.join(" ").trim_end())?;
+    }
+    let labels = (1..=num_stacks).map(|i| format!(" {} ", i)).collect::<Vec<_>>();
+    writeln!(out, "{}", labels.join(" "))?;
+    writeln!(out)?;
+    for _ in 0..size {
+        let sources = (0..num_stacks).filter(|x| !stacks[*x].is_empty()).collect::<Vec<_>>();
+        let source = *rng.choose(&sources);
+        let mut destination = rng.below(num_stacks as u64 - 1) as usize;
+        if destination >= source {
+            destination += 1;
+        }
+        // edge programs always move whole stacks
+        let amount = if edge {
+            stacks[source].len()
+        } else {
+            rng.between(1, stacks[source].len() as u64) as usize
+        };
+        let keep = stacks[source].len() - amount;
+        let moved = stacks[source].split_off(keep);
+        stacks[destination].extend(moved);
+        writeln!(out, "move {} from {} to {}", amount, source + 1, destination + 1)?;
+    }
+    Ok(())
+}
+
+// Background noise with no markers at all, then a run of fourteen distinct
+// chars so that both kinds of marker exist. Edge signals use a cycle of
+// thirteen chars, which is full of start-of-packet markers and start-of-
+// message near misses, with the run at the very end.
+fn day6(out: &mut impl Write, rng: &mut Rng, size: usize, edge: bool) -> io::Result<()> {
+    let size = size.max(14);
+    let mut signal = if edge {
+        (0..size - 14).map(|i| b'a' + (i % 13) as u8).collect::<Vec<_>>()
+    } else {
+        let noise = [b'x', b'y', b'z'];
+        (0..size - 14).map(|_| *rng.choose(&noise)).collect::<Vec<_>>()
+    };
+    let last = signal.last().copied();
+    let mut run = (b'a'..=b'w').filter(|x| Some(*x) != last).collect::<Vec<_>>();
+    rng.shuffle(&mut run);
+    run.truncate(14);
+    if edge {
+        // starting the run with the last background char keeps any earlier
+        // window from being a marker
+        if let Some(last) = last {
+            run[0] = last;
+        }
+    }
+    let at = if edge { signal.len() } else { rng.between(0, signal.len() as u64) as usize };
+    signal.splice(at..at, run);
+    writeln!(out, "{}", String::from_utf8(signal).unwrap())
+}
+
+// A file or directory in a generated tree. Nodes live in one flat arena and
+// refer to their children by index; directories have no size.
+struct Node {
+    name: String,
+    size: Option<usize>,
+    children: Vec<usize>
+}
+
+impl Node {
+    fn directory(name: &str) -> Node {
+        Node { name: name.to_owned(), size: None, children: Vec::new() }
+    }
+    fn is_directory(&self) -> bool {
+        self.size.is_none()
+    }
+}
+
+fn write_listing(out: &mut impl Write, nodes: &[Node], directory: usize) -> io::Result<()> {
+    writeln!(out, "$ ls")?;
+    for child in &nodes[directory].children {
+        match nodes[*child].size {
+            Some(size) => writeln!(out, "{} {}", size, nodes[*child].name)?,
+            None => writeln!(out, "dir {}", nodes[*child].name)?,
+        }
+    }
+    Ok(())
+}
+
+// Lists the tree depth first from the root, without recursion so that deep
+// trees cannot overflow the stack.
+fn write_transcript(out: &mut impl Write, nodes: &[Node], edge: bool) -> io::Result<()> {
+    writeln!(out, "$ cd /")?;
+    write_listing(out, nodes, 0)?;
+    // each directory being listed and how many of its children are done
+    let mut stack = vec![(0, 0)];
+    while let Some((directory, done)) = stack.last_mut() {
+        let children = &nodes[*directory].children;
+        match children[*done..].iter().position(|x| nodes[*x].is_directory()) {
+            Some(offset) => {
+                let child = children[*done + offset];
+                *done += offset + 1;
+                writeln!(out, "$ cd {}", nodes[child].name)?;
+                write_listing(out, nodes, child)?;
+                stack.push((child, 0));
+            },
+            None => {
+                stack.pop();
+                // edge transcripts sometimes go back up with "cd /"
+                if stack.len() == 1 && edge {
+                    writeln!(out, "$ cd /")?;
+                } else if !stack.is_empty() {
+                    writeln!(out, "$ cd ..")?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+fn random_name(rng: &mut Rng, file: bool) -> String {
+    let len = rng.between(1, 8);
+    let mut name = (0..len).map(|_| (b'a' + rng.below(26) as u8) as char).collect::<String>();
+    if file && rng.below(2) == 0 {
+        name += &format!(".{}", *rng.choose(&["txt", "dat", "log", "bin"]));
+    }
+    name
+}
+
+// A random tree listed depth first. The files add up to between 40,000,000
+// and the 70,000,000 of the day 7 disk, so that something has to be deleted
+// to make room but the free space sums cannot underflow. Edge trees fill the
+// disk exactly.
+fn day7(out: &mut impl Write, rng: &mut Rng, size: usize, edge: bool) -> io::Result<()> {
+    let mut nodes = vec![Node::directory("/")];
+    let mut directories = vec![0];
+    let mut names: HashSet<(usize, String)> = HashSet::new();
+    let mut files = Vec::new();
+    for i in 0..size {
+        // edge trees are a deep chain of directories all called "a", with
+        // empty directories and small files along the way
+        let parent = if edge { *directories.last().unwrap() } else { *rng.choose(&directories) };
+        let is_directory = if edge { i % 2 == 0 } else { rng.below(4) == 0 };
+        let name = if edge {
+            if is_directory { "a".to_owned() } else { format!("f{}", i) }
+        } else {
+            random_name(rng, !is_directory)
+        };
+        if !names.insert((parent, name.clone())) {
+            continue;
+        }
+        let index = nodes.len();
+        nodes[parent].children.push(index);
+        if is_directory {
+            directories.push(index);
+            nodes.push(Node::directory(&name));
+            if edge {
+                // an empty sibling
+                nodes[parent].children.push(index + 1);
+                nodes.push(Node::directory(&format!("e{}", i)));
+            }
+        } else {
+            files.push(index);
+            nodes.push(Node { name, size: Some(0), children: Vec::new() });
+        }
+    }
+    // Every file gets at least one, and the rest of the total is shared out
+    // by random weights, rounding so that the sizes add up exactly.
+    let disk: usize = 70000000;
+    let total = if edge { disk } else { rng.between(40000000, disk as u64) as usize }.max(files.len());
+    let weights = files.iter().map(|_| rng.between(1, 1000) as u128).collect::<Vec<_>>();
+    let weight_sum = weights.iter().sum::<u128>();
+    let spare = (total - files.len()) as u128;
+    let (mut weight_before, mut shared) = (0, 0);
+    for (file, weight) in files.iter().zip(weights) {
+        weight_before += weight;
+        let share = (spare * weight_before / weight_sum) as usize;
+        nodes[*file].size = Some(1 + share - shared);
+        shared = share;
+    }
+    write_transcript(out, &nodes, edge)
+}
+
+fn day8(out: &mut impl Write, rng: &mut Rng, size: usize, edge: bool) -> io::Result<()> {
+    let side = size.max(1);
+    for y in 0..side {
+        let row = (0..side)
+            .map(|x| {
+                // a stepped pyramid, full of equal heights blocking views
+                let height = if edge {
+                    x.min(y).min(side - 1 - x).min(side - 1 - y).min(9) as u32
+                } else {
+                    rng.below(10) as u32
+                };
+                std::char::from_digit(height, 10).unwrap()
+            })
+            .collect::<String>();
+        writeln!(out, "{}", row)?;
+    }
+    Ok(())
+}
+
+fn day9(out: &mut impl Write, rng: &mut Rng, size: usize, edge: bool) -> io::Result<()> {
+    let directions = ["L", "R", "U", "D"];
+    let mut previous = 0;
+    for i in 0..size {
+        // edge programs mix zero length moves, very long ones and
+        // immediate reversals
+        let (direction, distance) = if edge {
+            match i % 3 {
+                0 => (rng.below(4) as usize, 0),
+                1 => (rng.below(4) as usize, rng.between(1000, 100000)),
+                _ => (previous ^ 1, rng.between(1, 3)),
+            }
+        } else {
+            (rng.below(4) as usize, rng.between(1, 20))
+        };
+        previous = direction;
+        writeln!(out, "{} {}", directions[direction], distance)?;
+    }
+    Ok(())
+}
+
+fn generate(options: &GenOptions, out: &mut impl Write) -> io::Result<()> {
+    let mut rng = Rng::new(options.seed);
+    let (size, edge) = (options.size, options.edge);
+    match options.day {
+        1 => day1(out, &mut rng, size, edge),
+        2 => day2(out, &mut rng, size, edge),
+        3 => day3(out, &mut rng, size, edge),
+        4 => day4(out, &mut rng, size, edge),
+        5 => day5(out, &mut rng, size, edge),
+        6 => day6(out, &mut rng, size, edge),
+        7 => day7(out, &mut rng, size, edge),
+        8 => day8(out, &mut rng, size, edge),
+        _ => day9(out, &mut rng, size, edge),
+    }
+}
+
+pub fn gen(args: &[String]) {
+    let options = match GenOptions::parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: aoc2022 gen --day N [--size S] [--seed X] [--edge]");
+            std::process::exit(1);
+        },
+    };
+    let mut out = BufWriter::new(io::stdout().lock());
+    match generate(&options, &mut out).and_then(|_| out.flush()) {
+        // the reader has seen enough, as with "| head"
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {},
+        result => result.unwrap(),
+    }
+}
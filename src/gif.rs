@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+// Minimal animated GIF89a writer: one global palette, LZW compressed frames
+// that may cover a sub-rectangle of the screen, looping forever.
+
+pub struct GifEncoder<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+    depth: u8,
+}
+
+impl<W: Write> GifEncoder<W> {
+    pub fn new(mut out: W, width: u16, height: u16, palette: &[[u8; 3]]) -> io::Result<Self> {
+        if palette.is_empty() || palette.len() > 256 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Bad palette size"));
+        }
+        let mut depth = 1;
+        while (1 << depth) < palette.len() {
+            depth += 1;
+        }
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        out.write_all(&[0x80 | ((depth - 1) << 4) | (depth - 1), 0, 0])?;
+        for i in 0..(1 << depth) {
+            out.write_all(palette.get(i).unwrap_or(&[0, 0, 0]))?;
+        }
+        // NETSCAPE2.0 application extension: loop forever
+        out.write_all(&[0x21, 0xFF, 0x0B])?;
+        out.write_all(b"NETSCAPE2.0")?;
+        out.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+        Ok(GifEncoder { out, width, height, depth })
+    }
+
+    // Write a frame covering the rectangle at (x, y). Pixels are palette
+    // indices, row-major. The previous frame is left in place underneath.
+    pub fn frame(&mut self, x: u16, y: u16, w: u16, h: u16, pixels: &[u8], delay_cs: u16) -> io::Result<()> {
+        if pixels.len() != w as usize * h as usize
+            || x as usize + w as usize > self.width as usize
+            || y as usize + h as usize > self.height as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Bad frame"));
+        }
+        self.out.write_all(&[0x21, 0xF9, 0x04, 1 << 2])?;
+        self.out.write_all(&delay_cs.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+        self.out.write_all(&[0x2C])?;
+        for v in [x, y, w, h] {
+            self.out.write_all(&v.to_le_bytes())?;
+        }
+        self.out.write_all(&[0x00])?;
+        let min_code_size = self.depth.max(2);
+        self.out.write_all(&[min_code_size])?;
+        for block in lzw_encode(min_code_size, pixels).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    nbits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.acc |= (code as u32) << self.nbits;
+        self.nbits += size;
+        while self.nbits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.nbits -= 8;
+        }
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.nbits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw_encode(min_code_size: u8, pixels: &[u8]) -> Vec<u8> {
+    let clear: u16 = 1 << min_code_size;
+    let eoi = clear + 1;
+    let mut bits = BitWriter { bytes: Vec::new(), acc: 0, nbits: 0 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = eoi + 1;
+    let mut code_size = min_code_size + 1;
+    bits.write(clear, code_size);
+    let mut current: Option<u16> = None;
+    for &p in pixels {
+        let cur = match current {
+            None => {
+                current = Some(p as u16);
+                continue;
+            },
+            Some(cur) => cur,
+        };
+        if let Some(&code) = table.get(&(cur, p)) {
+            current = Some(code);
+            continue;
+        }
+        bits.write(cur, code_size);
+        if next == 4096 {
+            bits.write(clear, code_size);
+            table.clear();
+            next = eoi + 1;
+            code_size = min_code_size + 1;
+        } else {
+            if next >= (1 << code_size) {
+                code_size += 1;
+            }
+            table.insert((cur, p), next);
+            next += 1;
+        }
+        current = Some(p as u16);
+    }
+    if let Some(cur) = current {
+        bits.write(cur, code_size);
+    }
+    bits.write(eoi, code_size);
+    bits.finish()
+}
@@ -0,0 +1,161 @@
+use std::fs::File;
+use std::io::Read;
+use std::sync::OnceLock;
+
+use crate::utils::{AocError, AocResult, Source};
+
+// A whole puzzle input held once, handing out borrowed bytes, text and
+// lines. Files are memory mapped on 64-bit Linux, macOS and the BSDs, so
+// that inputs of hundreds of megabytes are paged in by the OS rather than
+// read and copied; elsewhere, and for stdin or text, the input is read into
+// memory in one go. Changing a file while it is mapped changes what the
+// solver sees, and truncating it kills the process, so inputs must not be
+// edited while being solved.
+pub struct Input {
+    data: Data
+}
+
+enum Data {
+    Mapped(sys::Mapping),
+    Owned(Vec<u8>),
+    Stdin(&'static [u8])
+}
+
+static STDIN: OnceLock<Vec<u8>> = OnceLock::new();
+
+// Stdin can only be read once, so it is kept for the rest of the run and
+// every op that asks for it sees the same input.
+pub fn stdin_bytes() -> AocResult<&'static [u8]> {
+    if let Some(buf) = STDIN.get() {
+        return Ok(buf);
+    }
+    let mut buf = Vec::new();
+    std::io::stdin().read_to_end(&mut buf)?;
+    Ok(STDIN.get_or_init(|| buf))
+}
+
+impl Input {
+    pub fn open(source: Source) -> AocResult<Input> {
+        let data = match source {
+            Source::File(filename) => {
+                let path = "./inputs/".to_owned() + filename;
+                Input::map_file(&path).map_err(|e| e.in_file(&path))?
+            },
+            Source::Text(text) => Data::Owned(text.as_bytes().to_vec()),
+            Source::Stdin => Data::Stdin(stdin_bytes()?),
+        };
+        Ok(Input { data })
+    }
+    fn map_file(path: &str) -> AocResult<Data> {
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| AocError::new(&format!("Input too large to map: {}", path)))?;
+        // mmap rejects empty mappings
+        if len == 0 {
+            return Ok(Data::Owned(Vec::new()));
+        }
+        Ok(Data::Mapped(sys::Mapping::new(&file, len)?))
+    }
+    pub fn bytes(&self) -> &[u8] {
+        match &self.data {
+            Data::Mapped(mapping) => mapping.bytes(),
+            Data::Owned(buf) => buf,
+            Data::Stdin(buf) => buf,
+        }
+    }
+    // Checks the input is utf-8 without copying it.
+    pub fn text(&self) -> AocResult<&str> {
+        std::str::from_utf8(self.bytes()).map_err(|e| AocError::new(&format!("Input is not utf-8: {}", e)))
+    }
+    pub fn lines(&self) -> AocResult<Lines<'_>> {
+        Ok(Lines { lines: self.text()?.lines(), line_no: 0 })
+    }
+}
+
+// Each line of an input with its line number, counting from 1.
+pub struct Lines<'a> {
+    lines: std::str::Lines<'a>,
+    line_no: usize
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = (usize, &'a str);
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+        self.line_no += 1;
+        Some((self.line_no, line))
+    }
+}
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "macos", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"),
+    target_pointer_width = "64"
+))]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::{c_int, c_void};
+    use std::os::unix::io::AsRawFd;
+
+    // The same values on Linux and the BSDs, including macOS, where off_t is
+    // 64 bits on 64-bit targets.
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+
+    extern "C" {
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    // A read-only private mapping of a whole file, unmapped on drop. The
+    // mapping stays valid after the file is closed.
+    pub struct Mapping {
+        ptr: *mut c_void,
+        len: usize
+    }
+
+    impl Mapping {
+        pub fn new(file: &File, len: usize) -> io::Result<Mapping> {
+            // SAFETY: a fresh mapping chosen by the OS aliases no Rust memory.
+            let ptr = unsafe { mmap(std::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0) };
+            if ptr as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Mapping { ptr, len })
+        }
+        pub fn bytes(&self) -> &[u8] {
+            // SAFETY: the mapping is len readable bytes until it is dropped.
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            // SAFETY: ptr and len came from a successful mmap.
+            unsafe { munmap(self.ptr, self.len); }
+        }
+    }
+}
+
+// Everywhere else the file is just read into memory.
+#[cfg(not(all(
+    any(target_os = "linux", target_os = "macos", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"),
+    target_pointer_width = "64"
+)))]
+mod sys {
+    use std::fs::File;
+    use std::io::{self, Read};
+
+    pub struct Mapping(Vec<u8>);
+
+    impl Mapping {
+        pub fn new(mut file: &File, len: usize) -> io::Result<Mapping> {
+            let mut buf = Vec::with_capacity(len);
+            file.read_to_end(&mut buf)?;
+            Ok(Mapping(buf))
+        }
+        pub fn bytes(&self) -> &[u8] {
+            &self.0
+        }
+    }
+}
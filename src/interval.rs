@@ -0,0 +1,352 @@
+use std::fmt::{self, Debug, Display, Formatter};
+use std::marker::PhantomData;
+
+// Integer types that can be used as interval bounds. Arithmetic is done in
+// i128 so that bounds at the edges of a type's range cannot overflow.
+pub trait Endpoint: Copy + Ord + Debug + Display {
+    fn to_i128(self) -> i128;
+    fn from_i128(x: i128) -> Self;
+}
+
+macro_rules! impl_endpoint {
+    ($($t:ty),*) => {
+        $(impl Endpoint for $t {
+            fn to_i128(self) -> i128 {
+                self as i128
+            }
+            fn from_i128(x: i128) -> Self {
+                x as $t
+            }
+        })*
+    }
+}
+
+impl_endpoint!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interval<T: Endpoint> {
+    // [from, to]
+    Closed(T, T),
+    // [from, to); day 4 only builds closed ranges
+    #[allow(dead_code)]
+    HalfOpen(T, T),
+}
+
+impl<T: Endpoint> Interval<T> {
+    // As a half open [from, to) in i128, empty if from >= to.
+    fn span(&self) -> (i128, i128) {
+        match *self {
+            Interval::Closed(from, to) => (from.to_i128(), to.to_i128() + 1),
+            Interval::HalfOpen(from, to) => (from.to_i128(), to.to_i128()),
+        }
+    }
+    fn from_span((from, to): (i128, i128)) -> Self {
+        Interval::Closed(T::from_i128(from), T::from_i128(to - 1))
+    }
+    // The first and last points in the interval, unless it is empty.
+    pub fn bounds(&self) -> Option<(T, T)> {
+        let (from, to) = self.span();
+        if from < to {
+            Some((T::from_i128(from), T::from_i128(to - 1)))
+        } else {
+            None
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bounds().is_none()
+    }
+    pub fn contains(&self, that: &Interval<T>) -> bool {
+        let (from, to) = self.span();
+        let (that_from, that_to) = that.span();
+        that.is_empty() || (from <= that_from && that_to <= to)
+    }
+    pub fn intersection(&self, that: &Interval<T>) -> Option<Interval<T>> {
+        let (from, to) = self.span();
+        let (that_from, that_to) = that.span();
+        let span = (from.max(that_from), to.min(that_to));
+        if span.0 < span.1 {
+            Some(Interval::from_span(span))
+        } else {
+            None
+        }
+    }
+    pub fn intersects(&self, that: &Interval<T>) -> bool {
+        self.intersection(that).is_some()
+    }
+}
+
+impl<T: Endpoint> Display for Interval<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Interval::Closed(from, to) => write!(f, "[{}, {}]", from, to),
+            Interval::HalfOpen(from, to) => write!(f, "[{}, {})", from, to),
+        }
+    }
+}
+
+// A set of points stored as sorted, disjoint, non-adjacent spans.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntervalSet<T: Endpoint> {
+    spans: Vec<(i128, i128)>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Endpoint> IntervalSet<T> {
+    pub fn new() -> Self {
+        IntervalSet { spans: Vec::new(), phantom: PhantomData }
+    }
+    fn from_spans(mut spans: Vec<(i128, i128)>) -> Self {
+        spans.retain(|(from, to)| from < to);
+        spans.sort();
+        let mut merged: Vec<(i128, i128)> = Vec::with_capacity(spans.len());
+        for (from, to) in spans {
+            match merged.last_mut() {
+                Some(last) if from <= last.1 => last.1 = last.1.max(to),
+                _ => merged.push((from, to)),
+            }
+        }
+        IntervalSet { spans: merged, phantom: PhantomData }
+    }
+    pub fn from_intervals<'a, I: IntoIterator<Item = &'a Interval<T>>>(intervals: I) -> Self where T: 'a {
+        IntervalSet::from_spans(intervals.into_iter().map(Interval::span).collect())
+    }
+    // Points covered by at least min_depth of the intervals.
+    pub fn depth_at_least<'a, I: IntoIterator<Item = &'a Interval<T>>>(intervals: I, min_depth: usize) -> Self where T: 'a {
+        let mut events = intervals.into_iter()
+            .map(Interval::span)
+            .filter(|(from, to)| from < to)
+            .flat_map(|(from, to)| [(from, 1), (to, -1)])
+            .collect::<Vec<(i128, i64)>>();
+        events.sort();
+        let mut spans = Vec::new();
+        let mut depth: i64 = 0;
+        let mut start = None;
+        for (x, delta) in events {
+            depth += delta;
+            match start {
+                None if depth >= min_depth as i64 => start = Some(x),
+                Some(from) if depth < min_depth as i64 => {
+                    spans.push((from, x));
+                    start = None;
+                },
+                _ => {},
+            }
+        }
+        IntervalSet::from_spans(spans)
+    }
+    // The greatest number of intervals covering any one point, and the
+    // points covered by that many.
+    pub fn max_depth<'a, I: IntoIterator<Item = &'a Interval<T>> + Clone>(intervals: I) -> (usize, Self) where T: 'a {
+        let mut events = intervals.clone().into_iter()
+            .map(Interval::span)
+            .filter(|(from, to)| from < to)
+            .flat_map(|(from, to)| [(from, 1), (to, -1)])
+            .collect::<Vec<(i128, i64)>>();
+        events.sort();
+        let mut depth: i64 = 0;
+        let mut max = 0;
+        for (_, delta) in events {
+            depth += delta;
+            max = max.max(depth);
+        }
+        if max == 0 {
+            return (0, IntervalSet::new());
+        }
+        (max as usize, IntervalSet::depth_at_least(intervals, max as usize))
+    }
+    // Add the interval in place, returning how many points were not already
+    // in the set.
+    pub fn insert(&mut self, interval: &Interval<T>) -> u128 {
+        let (from, to) = interval.span();
+        if from >= to {
+            return 0;
+        }
+        // spans that overlap or touch the interval
+        let first = self.spans.partition_point(|x| x.1 < from);
+        let last = self.spans.partition_point(|x| x.0 <= to);
+        let covered: i128 = self.spans[first..last].iter()
+            .map(|x| x.1.min(to) - x.0.max(from))
+            .filter(|x| *x > 0)
+            .sum();
+        let merged = match self.spans[first..last] {
+            [] => (from, to),
+            ref touching => (from.min(touching[0].0), to.max(touching[touching.len() - 1].1)),
+        };
+        self.spans.splice(first..last, [merged]);
+        (to - from - covered) as u128
+    }
+    pub fn contains_point(&self, x: T) -> bool {
+        let x = x.to_i128();
+        let i = self.spans.partition_point(|span| span.1 <= x);
+        i < self.spans.len() && self.spans[i].0 <= x
+    }
+    pub fn union(&self, that: &IntervalSet<T>) -> IntervalSet<T> {
+        IntervalSet::from_spans(self.spans.iter().chain(that.spans.iter()).copied().collect())
+    }
+    pub fn intersection(&self, that: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut spans = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.spans.len() && j < that.spans.len() {
+            let (a, b) = (self.spans[i], that.spans[j]);
+            let span = (a.0.max(b.0), a.1.min(b.1));
+            if span.0 < span.1 {
+                spans.push(span);
+            }
+            if a.1 < b.1 {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet::from_spans(spans)
+    }
+    pub fn difference(&self, that: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut spans = Vec::new();
+        let mut j = 0;
+        for &(from, to) in &self.spans {
+            let mut from = from;
+            while j < that.spans.len() && that.spans[j].1 <= from {
+                j += 1;
+            }
+            let mut k = j;
+            while k < that.spans.len() && that.spans[k].0 < to {
+                if that.spans[k].0 > from {
+                    spans.push((from, that.spans[k].0));
+                }
+                from = from.max(that.spans[k].1);
+                k += 1;
+            }
+            if from < to {
+                spans.push((from, to));
+            }
+        }
+        IntervalSet::from_spans(spans)
+    }
+    // Points within the bounds that are not in the set.
+    pub fn gaps(&self, within: &Interval<T>) -> IntervalSet<T> {
+        IntervalSet::from_intervals([within]).difference(self)
+    }
+    // Total number of points covered.
+    pub fn len(&self) -> u128 {
+        self.spans.iter().map(|(from, to)| (to - from) as u128).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = Interval<T>> + '_ {
+        self.spans.iter().map(|span| Interval::from_span(*span))
+    }
+}
+
+impl<T: Endpoint> Default for IntervalSet<T> {
+    fn default() -> Self {
+        IntervalSet::new()
+    }
+}
+
+impl<T: Endpoint> Display for IntervalSet<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let parts = self.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        write!(f, "{{{}}}", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set<T: Endpoint>(intervals: &[Interval<T>]) -> IntervalSet<T> {
+        IntervalSet::from_intervals(intervals)
+    }
+
+    fn list<T: Endpoint>(set: &IntervalSet<T>) -> Vec<Interval<T>> {
+        set.iter().collect()
+    }
+
+    #[test]
+    fn adjacent_and_overlapping_spans_merge() {
+        let merged = set(&[Interval::Closed(1, 3), Interval::Closed(4, 6), Interval::Closed(5, 9)]);
+        assert_eq!(list(&merged), vec![Interval::Closed(1, 9)]);
+        assert_eq!(merged.len(), 9);
+        let apart = set(&[Interval::Closed(1, 3), Interval::Closed(5, 6)]);
+        assert_eq!(list(&apart), vec![Interval::Closed(1, 3), Interval::Closed(5, 6)]);
+        assert_eq!(apart.union(&set(&[Interval::Closed(4, 4)])), set(&[Interval::Closed(1, 6)]));
+    }
+
+    #[test]
+    fn half_open_and_closed_agree() {
+        assert_eq!(set(&[Interval::HalfOpen(1, 4)]), set(&[Interval::Closed(1, 3)]));
+        assert_eq!(Interval::HalfOpen(1, 4).bounds(), Some((1, 3)));
+        assert!(Interval::HalfOpen(3, 3).is_empty());
+        assert!(Interval::Closed(3, 2).is_empty());
+        assert!(!Interval::Closed(3, 3).is_empty());
+        assert!(set(&[Interval::HalfOpen(3, 3)]).is_empty());
+        assert!(Interval::Closed(1, 3).contains(&Interval::HalfOpen(1, 4)));
+        assert!(!Interval::HalfOpen(1, 3).contains(&Interval::Closed(1, 3)));
+        assert!(!Interval::HalfOpen(1, 3).intersects(&Interval::Closed(3, 5)));
+        assert_eq!(Interval::Closed(1, 3).intersection(&Interval::HalfOpen(3, 5)), Some(Interval::Closed(3, 3)));
+    }
+
+    #[test]
+    fn insert_in_place() {
+        let mut spans = set(&[Interval::Closed(1, 3), Interval::Closed(8, 9)]);
+        assert_eq!(spans.insert(&Interval::Closed(4, 4)), 1);
+        assert_eq!(spans.insert(&Interval::Closed(2, 10)), 4);
+        assert_eq!(spans.insert(&Interval::HalfOpen(5, 5)), 0);
+        assert_eq!(list(&spans), vec![Interval::Closed(1, 10)]);
+        assert_eq!(spans.insert(&Interval::Closed(-5, -4)), 2);
+        assert!(spans.contains_point(-4) && spans.contains_point(10));
+        assert!(!spans.contains_point(-3) && !spans.contains_point(11));
+        let mut ends = IntervalSet::new();
+        assert_eq!(ends.insert(&Interval::Closed(u8::MIN, u8::MAX)), 256);
+        assert!(ends.contains_point(255));
+    }
+
+    #[test]
+    fn set_algebra() {
+        let a = set(&[Interval::Closed(1, 10)]);
+        let b = set(&[Interval::Closed(3, 4), Interval::Closed(8, 12)]);
+        assert_eq!(list(&a.intersection(&b)), vec![Interval::Closed(3, 4), Interval::Closed(8, 10)]);
+        assert_eq!(list(&a.difference(&b)), vec![Interval::Closed(1, 2), Interval::Closed(5, 7)]);
+        assert_eq!(list(&b.difference(&a)), vec![Interval::Closed(11, 12)]);
+        assert_eq!(list(&a.union(&b)), vec![Interval::Closed(1, 12)]);
+        assert!(a.difference(&a).is_empty());
+        assert_eq!(list(&b.gaps(&Interval::Closed(0, 14))),
+            vec![Interval::Closed(0, 2), Interval::Closed(5, 7), Interval::Closed(13, 14)]);
+    }
+
+    #[test]
+    fn depth() {
+        let ranges = [Interval::Closed(1, 5), Interval::Closed(3, 7), Interval::Closed(4, 4), Interval::Closed(6, 9), Interval::Closed(11, 12), Interval::Closed(12, 13)];
+        assert_eq!(list(&IntervalSet::depth_at_least(&ranges, 2)), vec![Interval::Closed(3, 7), Interval::Closed(12, 12)]);
+        assert_eq!(list(&IntervalSet::depth_at_least(&ranges, 3)), vec![Interval::Closed(4, 4)]);
+        assert!(IntervalSet::depth_at_least(&ranges, 4).is_empty());
+        assert_eq!(IntervalSet::max_depth(&ranges), (3, set(&[Interval::Closed(4, 4)])));
+        // ranges that only touch do not overlap
+        let touching = [Interval::HalfOpen(1, 3), Interval::HalfOpen(3, 5)];
+        assert_eq!(IntervalSet::max_depth(&touching), (1, set(&[Interval::Closed(1, 4)])));
+        assert_eq!(IntervalSet::<i32>::max_depth(&[]), (0, IntervalSet::new()));
+    }
+
+    #[test]
+    fn u8_limits() {
+        let all = Interval::Closed(u8::MIN, u8::MAX);
+        assert_eq!(set(&[all]).len(), 256);
+        assert_eq!(Interval::HalfOpen(0u8, 255).bounds(), Some((0, 254)));
+        let ends = set(&[Interval::Closed(0u8, 0), Interval::Closed(255, 255)]);
+        assert_eq!(list(&ends.gaps(&all)), vec![Interval::Closed(1, 254)]);
+        assert_eq!(list(&ends.union(&set(&[Interval::Closed(1, 254)]))), vec![all]);
+        assert!(set(&[all]).difference(&set(&[all])).is_empty());
+    }
+
+    #[test]
+    fn i64_limits() {
+        let all = Interval::Closed(i64::MIN, i64::MAX);
+        assert_eq!(set(&[all]).len(), 1 << 64);
+        let top = set(&[Interval::Closed(i64::MAX - 1, i64::MAX)]);
+        assert_eq!(top.len(), 2);
+        assert_eq!(list(&set(&[all]).difference(&top)), vec![Interval::Closed(i64::MIN, i64::MAX - 2)]);
+        assert_eq!(list(&top.gaps(&Interval::HalfOpen(i64::MAX - 3, i64::MAX))), vec![Interval::Closed(i64::MAX - 3, i64::MAX - 2)]);
+        assert_eq!(Interval::Closed(i64::MIN, i64::MIN).intersection(&all), Some(Interval::Closed(i64::MIN, i64::MIN)));
+    }
+}
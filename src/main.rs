@@ -7,34 +7,80 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+mod generator;
+mod gif;
+mod input;
+mod interval;
 mod utils;
 
+use crate::day1::day1;
 use crate::day1::day1_part1;
 use crate::day1::day1_part2;
+use crate::day1::day1_plan;
+use crate::day1::day1_query;
+use crate::day1::day1_stats;
 use crate::day2::day2;
+use crate::day2::day2_play;
+use crate::day2::day2_report;
+use crate::day2::day2_solve;
+use crate::day2::day2_tournament;
 use crate::day3::day3;
+use crate::day3::day3_groups;
+use crate::day3::day3_report;
 use crate::day4::day4;
+use crate::day4::day4_coverage;
+use crate::day4::day4_sweep;
+use crate::day4::day4_optimise;
+use crate::day4::day4_validate;
 use crate::day5::day5;
 use crate::day6::day6;
+use crate::day6::day6_frames;
+use crate::day6::day6_markers;
 use crate::day7::day7;
 use crate::day8::day8;
 use crate::day9::day9;
+use crate::day9::day9_animate;
+use crate::day9::day9_gif;
+use crate::day9::day9_knots;
+use crate::day9::day9_multi;
+use crate::day9::day9_stream;
 
 struct Op {
     name: &'static str,
     action: fn() -> ()
 }
 
-static OPS: [Op; 10] = [
+static OPS: [Op; 31] = [
+    Op{name:"day1", action: day1},
     Op{name:"day1_part1", action: day1_part1},
     Op{name:"day1_part2", action: day1_part2},
+    Op{name:"day1_stats", action: day1_stats},
+    Op{name:"day1_query", action: day1_query},
+    Op{name:"day1_plan", action: day1_plan},
     Op{name:"day2", action: day2},
+    Op{name:"day2_solve", action: day2_solve},
+    Op{name:"day2_tournament", action: day2_tournament},
+    Op{name:"day2_play", action: day2_play},
+    Op{name:"day2_report", action: day2_report},
     Op{name:"day3", action: day3},
+    Op{name:"day3_groups", action: day3_groups},
+    Op{name:"day3_report", action: day3_report},
     Op{name:"day4", action: day4},
+    Op{name:"day4_coverage", action: day4_coverage},
+    Op{name:"day4_sweep", action: day4_sweep},
+    Op{name:"day4_validate", action: day4_validate},
+    Op{name:"day4_optimise", action: day4_optimise},
     Op{name:"day5", action: day5},
     Op{name:"day6", action: day6},
+    Op{name:"day6_markers", action: day6_markers},
+    Op{name:"day6_frames", action: day6_frames},
     Op{name:"day7", action: day7},
     Op{name:"day8", action: day8},
+    Op{name:"day9_animate", action: day9_animate},
+    Op{name:"day9_gif", action: day9_gif},
+    Op{name:"day9_knots", action: day9_knots},
+    Op{name:"day9_multi", action: day9_multi},
+    Op{name:"day9_stream", action: day9_stream},
     Op{name:"day9", action: day9},
 ];
 
@@ -49,11 +95,14 @@ fn execute(action: &str) {
 }
 
 fn main() {
-    if std::env::args().count() == 1 {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("gen") {
+        generator::gen(&args[1..]);
+    } else if args.is_empty() {
         execute(&OPS.last().unwrap().name);
     } else {
-        for arg in std::env::args().skip(1) {
-            execute(&arg);
+        for arg in &args {
+            execute(arg);
         }
     }
 }
\ No newline at end of file
@@ -1,58 +1,45 @@
 
-use std::io;
-use std::io::BufRead;
-use std::fs::File;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
-use std::io::Read;
-//use std::str::FromStr;
-
-pub fn read_data_lines(filename: &str) -> io::Result<Vec<String>> {
-    let path = "./inputs/".to_owned() + filename;
-    let file = File::open(path)?;
-    io::BufReader::new(file).lines().collect::<Result<_, _>>()
-}
-
-pub fn read_data_text(filename: &str) -> io::Result<String> {
-    let path = "./inputs/".to_owned() + filename;
-    let file = File::open(path)?;
-    let mut buf: String = "".to_string();
-    io::BufReader::new(file).read_to_string(&mut buf)?;
-    Ok(buf)
-}
-
-//pub fn read_data_records<T: FromStr, E = io::Error>(filename: &str) -> Result<Vec<T>, E>
-//where E: From<T::Err> + From<io::Error> + Into<io::Error>
-//{
-//    let lines = read_data_lines(filename)?;
-//    lines.iter()
-//        .map(|x| x.trim().to_owned())
-//        .filter(|x| !x.is_empty())
-//        .map(|x| x.parse::<T>() )
-//        .collect::<Result<Vec<T>>, E>()
-//}
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use crate::input::{Input, Lines};
 
 #[derive(Debug)]
 pub struct AocError {
-    details: String
+    details: String,
+    // which file and where in it the error is, 1-based, when known
+    file: Option<String>,
+    line: Option<usize>,
+    column: Option<usize>
 }
 
 pub type AocResult<T> = Result<T, AocError>;
 
 impl From<std::io::Error> for AocError {
     fn from(e: std::io::Error) -> Self {
-        AocError { details: e.to_string() }
+        AocError::new(&e.to_string())
     }
 }
 
 impl From<std::num::ParseIntError> for AocError {
     fn from(e: std::num::ParseIntError) -> Self {
-        AocError { details: e.to_string() }
+        AocError::new(&e.to_string())
     }
 }
 
 impl Display for AocError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file)?;
+        }
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "line {}, column {}: ", line, column)?,
+            (Some(line), None) => write!(f, "line {}: ", line)?,
+            (None, Some(column)) => write!(f, "column {}: ", column)?,
+            (None, None) => {},
+        }
         write!(f, "{}", self.details)
     }
 }
@@ -65,6 +52,261 @@ impl Error for AocError {
 
 impl AocError {
     pub fn new(msg: &str) -> Self {
-        Self{details: msg.to_string()}
+        Self{details: msg.to_string(), file: None, line: None, column: None}
+    }
+    pub fn in_file(self, file: &str) -> Self {
+        Self{file: self.file.or(Some(file.to_owned())), ..self}
+    }
+    // Errors keep the innermost line number they are given.
+    pub fn at_line(self, line: usize) -> Self {
+        Self{line: self.line.or(Some(line)), ..self}
+    }
+    // Place the error in text starting at the given column. An error that
+    // already has a column, relative to that text, is moved along by it.
+    pub fn at_column(self, column: usize) -> Self {
+        Self{column: Some(self.column.map_or(column, |x| x + column - 1)), ..self}
+    }
+    fn shifted(self, chars: usize) -> Self {
+        Self{column: self.column.map(|x| x + chars), ..self}
+    }
+}
+
+// A line pattern such as "move {amount} from {source} to {destination}".
+// Text outside braces must appear exactly. Each {name} captures everything
+// up to the next piece of literal text, or the rest of the line if it comes
+// last, and must not be empty. Names are only used in error messages.
+#[derive(Clone, Copy)]
+pub struct Pattern(pub &'static str);
+
+pub struct Captures<'a> {
+    // name, column and text of each field
+    fields: Vec<(&'static str, usize, &'a str)>
+}
+
+impl Pattern {
+    pub fn captures<'a>(&self, s: &'a str) -> AocResult<Captures<'a>> {
+        let column = |pos: usize| s[..pos].chars().count() + 1;
+        let mut fields = Vec::new();
+        let mut pos = 0;
+        let mut rest = self.0;
+        let mut pending: Option<&'static str> = None;
+        loop {
+            let (literal, name) = match rest.find('{') {
+                Some(open) => {
+                    let close = open + rest[open..].find('}').expect("Unclosed field in pattern");
+                    let parts = (&rest[..open], Some(&rest[open + 1..close]));
+                    rest = &rest[close + 1..];
+                    parts
+                },
+                None => (rest, None),
+            };
+            if let Some(pending) = pending.take() {
+                let end = if literal.is_empty() {
+                    assert!(name.is_none(), "Adjacent fields in pattern");
+                    s.len()
+                } else {
+                    match s[pos..].find(literal) {
+                        Some(i) => pos + i,
+                        None => return Err(AocError::new(&format!("expected {:?} after {}", literal, pending))
+                            .at_column(column(s.len()))),
+                    }
+                };
+                if end == pos {
+                    return Err(AocError::new(&format!("missing {}", pending)).at_column(column(pos)));
+                }
+                fields.push((pending, column(pos), &s[pos..end]));
+                pos = end;
+            }
+            if !s[pos..].starts_with(literal) {
+                let matched = s[pos..].char_indices()
+                    .zip(literal.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map_or(s.len() - pos, |((i, _), _)| i);
+                return Err(AocError::new(&format!("expected {:?}", literal)).at_column(column(pos + matched)));
+            }
+            pos += literal.len();
+            match name {
+                Some(name) => pending = Some(name),
+                None => break,
+            }
+        }
+        if pos != s.len() {
+            return Err(AocError::new(&format!("unexpected {:?}", &s[pos..])).at_column(column(pos)));
+        }
+        Ok(Captures { fields })
+    }
+}
+
+impl<'a> Captures<'a> {
+    pub fn get(&self, i: usize) -> &'a str {
+        self.fields[i].2
+    }
+    // Apply f to a field, placing any error at the field's column.
+    pub fn with<T>(&self, i: usize, f: impl FnOnce(&'a str) -> AocResult<T>) -> AocResult<T> {
+        f(self.fields[i].2).map_err(|e| e.at_column(self.fields[i].1))
+    }
+    pub fn parse<T: FromStr>(&self, i: usize) -> AocResult<T> where T::Err: Display {
+        let name = self.fields[i].0;
+        self.with(i, |s| s.parse::<T>()
+            .map_err(|e| AocError::new(&format!("bad {}: {}: {:?}", name, e, s))))
+    }
+}
+
+// Parse a line with f, ignoring surrounding whitespace but keeping error
+// columns relative to the whole line.
+pub fn parse_record<T>(line: &str, f: impl FnOnce(&str) -> AocResult<T>) -> AocResult<T> {
+    let line = line.trim_end();
+    let trimmed = line.trim_start();
+    let indent = line[..line.len() - trimmed.len()].chars().count();
+    f(trimmed).map_err(|e| e.shifted(indent))
+}
+
+// Parse each non-blank line with f as it is reached, placing errors at the
+// line's number.
+pub fn parse_numbered<'a, I, T, F>(lines: I, mut f: F) -> impl Iterator<Item = AocResult<T>> + use<'a, I, T, F>
+where I: Iterator<Item = (usize, &'a str)>, F: FnMut(&str) -> AocResult<T> {
+    lines.filter(|(_, x)| !x.trim().is_empty())
+        .map(move |(line_no, x)| parse_record(x, &mut f).map_err(|e| e.at_line(line_no)))
+}
+
+// Parse each char of a line with f, placing errors at their column.
+pub fn parse_chars<T>(s: &str, mut f: impl FnMut(char) -> AocResult<T>) -> AocResult<Vec<T>> {
+    s.chars()
+        .enumerate()
+        .map(|(i, c)| f(c).map_err(|e| e.at_column(i + 1)))
+        .collect()
+}
+
+// A rectangular grid with one row per non-blank numbered line.
+pub fn parse_grid<'a, T>(lines: impl Iterator<Item = (usize, &'a str)>, mut f: impl FnMut(char) -> AocResult<T>) -> AocResult<Vec<Vec<T>>> {
+    let mut width = None;
+    parse_numbered(lines, |line| {
+        let row = parse_chars(line, &mut f)?;
+        match width {
+            Some(w) if w != row.len() => Err(AocError::new(&format!("expected {} cells, found {}", w, row.len()))),
+            _ => {
+                width = Some(row.len());
+                Ok(row)
+            },
+        }
+    }).collect()
+}
+
+// Where puzzle input comes from: a file in the inputs directory, text
+// already in memory, or standard input.
+pub enum Source<'a> {
+    File(&'a str),
+    Text(&'a str),
+    Stdin
+}
+
+// A day's input file, or standard input if AOC_INPUT is "-", so that inputs
+// can be piped in.
+pub fn input_source(filename: &str) -> Source<'_> {
+    match std::env::var("AOC_INPUT").as_deref() {
+        Ok("-") => Source::Stdin,
+        _ => Source::File(filename),
     }
-}
\ No newline at end of file
+}
+
+// The environment variable parsed as a T, or the default if it is unset.
+pub fn env_parse<T: FromStr>(name: &str, default: &str) -> AocResult<T> where T::Err: Display {
+    let value = std::env::var(name).unwrap_or(default.to_owned());
+    value.parse().map_err(|e| AocError::new(&format!("Bad value for {}: {:?} ({})", name, value, e)))
+}
+
+fn parse_line<T: FromStr>(line: &str) -> AocResult<T> where T::Err: Into<AocError> {
+    line.parse::<T>().map_err(Into::into)
+}
+
+// Every non-blank line parsed as a T.
+pub fn read_records<T: FromStr>(source: Source) -> AocResult<Vec<T>> where T::Err: Into<AocError> {
+    parse_numbered(Input::open(source)?.lines()?, parse_line::<T>).collect()
+}
+
+// Records from a day's input, which may be piped in; see input_source.
+pub fn read_data_records<T: FromStr>(filename: &str) -> AocResult<Vec<T>> where T::Err: Into<AocError> {
+    read_records(input_source(filename))
+}
+
+// Records in groups separated by runs of blank lines, read one group at a
+// time so that only the current group is held in memory.
+pub struct RecordGroups<'a, T> {
+    lines: Lines<'a>,
+    phantom: PhantomData<T>
+}
+
+impl<'a, T> RecordGroups<'a, T> {
+    pub fn new(lines: Lines<'a>) -> Self {
+        RecordGroups { lines, phantom: PhantomData }
+    }
+}
+
+impl<T: FromStr> Iterator for RecordGroups<'_, T> where T::Err: Into<AocError> {
+    type Item = AocResult<Vec<T>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut group = Vec::new();
+        for (line_no, line) in self.lines.by_ref() {
+            if line.trim().is_empty() {
+                if !group.is_empty() {
+                    return Some(Ok(group));
+                }
+                continue;
+            }
+            match parse_record(line, parse_line::<T>) {
+                Ok(record) => group.push(record),
+                Err(e) => return Some(Err(e.at_line(line_no))),
+            }
+        }
+        if group.is_empty() { None } else { Some(Ok(group)) }
+    }
+}
+
+// The lines before the first blank line are returned as they are, for
+// headers such as drawings where the layout matters, and the rest are
+// parsed as records.
+pub fn read_header_and_records<T: FromStr>(source: Source) -> AocResult<(Vec<String>, Vec<T>)> where T::Err: Into<AocError> {
+    let input = Input::open(source)?;
+    let mut lines = input.lines()?;
+    let header = lines.by_ref()
+        .take_while(|(_, x)| !x.trim().is_empty())
+        .map(|(_, x)| x.to_string())
+        .collect();
+    let records = parse_numbered(lines, parse_line::<T>).collect::<AocResult<_>>()?;
+    Ok((header, records))
+}
+
+// Small seeded pseudo-random generator (splitmix64), so that runs can be
+// repeated exactly.
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+    // Uniform in 0..n, n must be non-zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+    // Uniform in from..=to.
+    pub fn between(&mut self, from: u64, to: u64) -> u64 {
+        from + self.below(to - from + 1)
+    }
+    pub fn choose<'a, T>(&mut self, values: &'a [T]) -> &'a T {
+        &values[self.below(values.len() as u64) as usize]
+    }
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            values.swap(i, self.below(i as u64 + 1) as usize);
+        }
+    }
+}